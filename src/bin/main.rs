@@ -1,89 +1,90 @@
 use std::env;
+use std::mem;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 use ggez::GameResult;
-use nalgebra::Vector3;
-use serde_yaml::Value;
 
-use rust_tracer::{window, config, buffer};
-use rust_tracer::tracer::{Camera, SceneState, PointLight};
-use rust_tracer::tracer::geom::sphere::Sphere;
-use rust_tracer::tracer::geom::Drawable;
-use rust_tracer::tracer::colour::RGB;
-use rust_tracer::tracer::geom::plane::Plane;
-use rand::seq::index::sample;
-use std::sync::RwLock;
-
-fn unwrap_xyz(xyz: &serde_yaml::Value) -> Vector3<f64> {
-    nalgebra::Vector3::new(
-        xyz["x"].as_f64().unwrap(),
-        xyz["y"].as_f64().unwrap(),
-        xyz["z"].as_f64().unwrap(),
-    )
-}
-
-fn unwrap_rgb(rgb: &serde_yaml::Value) -> RGB {
-    RGB {
-        r: rgb["r"].as_u64().unwrap() as u8,
-        g: rgb["g"].as_u64().unwrap() as u8,
-        b: rgb["b"].as_u64().unwrap() as u8,
-    }
-}
+use rust_tracer::{window, config, buffer, output, scene};
+use rust_tracer::tracer::debug::NanCheck;
+use rust_tracer::tracer::integrator::IntegratorRegistry;
+use rust_tracer::tracer::phong::Phong;
 
 pub fn main() -> GameResult {
     let args: Vec<String> = env::args().collect();
     let config = config::parse_args(args);
-    let scene_file = std::fs::File::open(&config.filename)?;
-    let deserialised: Value = serde_yaml::from_reader(&scene_file).unwrap();
-
-    let camera = &deserialised["camera"];
-    let camera = Camera {
-        pos: unwrap_xyz(&camera["pos"]),
-        dir: unwrap_xyz(&camera["dir"]),
-        fov: camera["fov"].as_f64().unwrap()
+    let integrators = IntegratorRegistry::default();
+    let mut scene = match scene::load_with(&config.filename, &integrators) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("Couldn't load scene {}", err);
+            process::exit(1);
+        }
     };
-    let mut geom: Vec<Box<dyn Drawable>> = vec![];
-    let mut point_lights: Vec<PointLight> = vec![];
-
-    for sphere in deserialised["spheres"].as_sequence().unwrap() {
-        geom.push(Box::new(Sphere::new (
-            unwrap_xyz(&sphere["pos"]),
-            sphere["radius"].as_f64().unwrap(),
-            unwrap_rgb(&sphere["material"]["colour"]),
-            sphere["material"]["diffuse"].as_f64().unwrap(),
-           sphere["material"]["specular"].as_f64().unwrap(),
-            sphere["material"]["specular_exp"].as_f64().unwrap()
-            )
-        ));
+    if let Some(tone_map) = config.tone_map {
+        scene.display.tone_map = tone_map;
     }
-
-    for plane in deserialised["planes"].as_sequence().unwrap() {
-        geom.push(Box::new(Plane::new (
-            unwrap_xyz(&plane["pos"]),
-            unwrap_xyz(&plane["norm"]),
-            unwrap_rgb(&plane["material"]["colour"]),
-            plane["material"]["diffuse"].as_f64().unwrap(),
-            plane["material"]["specular"].as_f64().unwrap(),
-            plane["material"]["specular_exp"].as_f64().unwrap()
-        )));
+    if let Some(exposure) = config.exposure {
+        scene.display.exposure = exposure;
     }
-
-    for point_light in deserialised["point_lights"].as_sequence().unwrap() {
-        point_lights.push(PointLight {
-            pos: unwrap_xyz(&point_light["pos"]),
-            colour: unwrap_rgb(&point_light["colour"]),
-            intensity: point_light["intensity"].as_f64().unwrap()
+    if let Some(name) = &config.integrator {
+        scene.integrator = integrators.create(name).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
         });
     }
-    let ambient = deserialised["ambient"].as_f64().unwrap();
-    let background = unwrap_rgb(&deserialised["background"]);
-    let scene = SceneState { geom, point_lights, camera, ambient, background_colour: background };
-    let mut frame_buffer = buffer::FrameBuffer::new(config.resolution);
+    if let Some(sampler) = config.sampler {
+        scene.sampler = sampler.sampler();
+    }
+    if let Some(seed) = config.seed {
+        scene.seed = seed;
+    }
+    if config.flag_nan {
+        let inner = mem::replace(&mut scene.integrator, Box::new(Phong));
+        scene.integrator = Box::new(NanCheck { inner });
+    }
+    match scene.extent() {
+        Some(extent) => println!(
+            "Scene has {} objects ({} unbounded), extent ({:.2}, {:.2}, {:.2}) to ({:.2}, {:.2}, {:.2})",
+            scene.geom().len(), scene.unbounded_count(),
+            extent.min[0], extent.min[1], extent.min[2], extent.max[0], extent.max[1], extent.max[2]
+        ),
+        None => println!("Scene has {} objects, all unbounded", scene.geom().len()),
+    }
+    if let Some(output) = &config.output {
+        let frame_buffer = buffer::FrameBuffer::with_aovs(config.resolution, &config.aovs);
+        let start = Instant::now();
+        scene.render(&frame_buffer, config.samples, config.threads);
+        println!("Render finished in {:?}", start.elapsed());
 
+        let path = Path::new(output);
+        let aov_layers = !config.aov_files && output::OutputFormat::from_path(path)? == output::OutputFormat::Exr;
+        output::write_image(path, &frame_buffer, config.exr_precision, aov_layers)?;
+        println!("Wrote {}", output);
+        if !aov_layers {
+            for aov_path in output::write_aov_images(path, &frame_buffer, config.exr_precision, &scene.display)? {
+                println!("Wrote {}", aov_path.display());
+            }
+        }
+        return Ok(());
+    }
+    if !config.aovs.is_empty() {
+        eprintln!("AOVs are only written when rendering to a file with --output");
+    }
+
+    let frame_buffer = Arc::new(buffer::FrameBuffer::new(config.resolution));
     {
-        let mut write_target = frame_buffer.write_buffer().write().unwrap();
-        scene.rasterise((*write_target).as_mut(), &config.resolution, config.samples);
+        let frame_buffer = Arc::clone(&frame_buffer);
+        let (samples, threads) = (config.samples, config.threads);
+        thread::spawn(move || {
+            let start = Instant::now();
+            scene.render_progressive(&frame_buffer, samples, threads);
+            println!("Render finished in {:?}", start.elapsed());
+        });
     }
-    frame_buffer.swap();
 
     window::run(config.resolution, &frame_buffer)
 }
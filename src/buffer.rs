@@ -1,45 +1,133 @@
 use std::convert::TryFrom;
 use crate::Resolution;
-use std::sync::RwLock;
+use crate::tracer::aov::Aov;
+use crate::tracer::colour::Colour;
+use crate::tracer::tile::{self, Tile};
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+/// Double buffered RGBA frame. Renderers write into the write (rear) buffer and publish it,
+/// while the window reads whatever was last published from the read (front) buffer. The linear
+/// radiance behind the displayed pixels is kept alongside for writing out float images, as are
+/// any AOVs asked for.
 pub struct FrameBuffer {
     res: Resolution,
     front_buffer: RwLock<Vec<u8>>,
     rear_buffer: RwLock<Vec<u8>>,
-    swap: bool,
+    radiance: RwLock<Vec<Colour>>,
+    aovs: Vec<Aov>,
+    aov_buffers: Vec<RwLock<Vec<Colour>>>,
+    swap: AtomicBool,
+    //Held while writing into the rear buffer or swapping so a publish can't lose a tile
+    publish_lock: Mutex<()>,
 }
 
 impl FrameBuffer {
     pub fn new(res: Resolution) -> FrameBuffer {
+        FrameBuffer::with_aovs(res, &[])
+    }
+
+    pub fn with_aovs(res: Resolution, aovs: &[Aov]) -> FrameBuffer {
         let byte_stride = 4;
         let pixels: u32 = res.width as u32 * res.height as u32;
-        let mut front_buffer: Vec<u8> = vec![255; usize::try_from(pixels * byte_stride).unwrap()];
-        let mut rear_buffer: Vec<u8> = vec![255; usize::try_from(pixels * byte_stride).unwrap()];
+        let front_buffer: Vec<u8> = vec![255; usize::try_from(pixels * byte_stride).unwrap()];
+        let rear_buffer: Vec<u8> = vec![255; usize::try_from(pixels * byte_stride).unwrap()];
 
         FrameBuffer {
             res,
             front_buffer: RwLock::new(front_buffer),
             rear_buffer: RwLock::new(rear_buffer),
-            swap: true
+            radiance: RwLock::new(vec![Colour::black(); pixels as usize]),
+            aovs: aovs.to_vec(),
+            aov_buffers: aovs.iter().map(|_| RwLock::new(vec![Colour::black(); pixels as usize])).collect(),
+            swap: AtomicBool::new(true),
+            publish_lock: Mutex::new(()),
         }
     }
 
+    pub fn resolution(&self) -> Resolution {
+        self.res
+    }
+
     pub fn read_buffer(&self) -> &RwLock<Vec<u8>> {
-        if self.swap {
+        if self.swap.load(Ordering::Acquire) {
             &self.front_buffer
         } else {
             &self.rear_buffer
         }
     }
     pub fn write_buffer(&self) -> &RwLock<Vec<u8>> {
-        if self.swap {
+        if self.swap.load(Ordering::Acquire) {
             &self.rear_buffer
         } else {
             &self.front_buffer
         }
     }
 
-    pub fn swap(&mut self) {
-        self.swap = !self.swap;
+    /// Unclamped linear radiance for each pixel, row by row from the top left.
+    pub fn radiance(&self) -> &RwLock<Vec<Colour>> {
+        &self.radiance
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    /// Per pixel values of an AOV, laid out like the radiance. Single channel AOVs only use `r`.
+    pub fn aov(&self, aov: Aov) -> Option<&RwLock<Vec<Colour>>> {
+        self.aovs.iter().position(|&known| known == aov).map(|index| &self.aov_buffers[index])
+    }
+
+    pub fn swap(&self) {
+        self.swap.fetch_xor(true, Ordering::AcqRel);
+    }
+
+    /// Copies a finished tile's display pixels into its region of the write buffer, its radiance
+    /// into the radiance buffer and its AOVs, in the same order as `aovs`, into theirs.
+    pub fn write_tile(&self, tile: &Tile, pixels: &[u8], radiance: &[Colour], aovs: &[Vec<Colour>]) {
+        tile::copy_to_frame(tile, radiance, &mut self.radiance.write().unwrap(), &self.res, 1);
+        for (buffer, values) in self.aov_buffers.iter().zip(aovs) {
+            tile::copy_to_frame(tile, values, &mut buffer.write().unwrap(), &self.res, 1);
+        }
+        let _guard = self.publish_lock.lock().unwrap();
+        let mut write_target = self.write_buffer().write().unwrap();
+        tile::copy_to_frame(tile, pixels, &mut write_target, &self.res, 4);
+    }
+
+    /// Swaps the buffers so readers see everything written so far, then brings the new write
+    /// buffer up to date so later tiles land on top of the published image.
+    pub fn publish(&self) {
+        let _guard = self.publish_lock.lock().unwrap();
+        self.swap();
+        let published = self.read_buffer().read().unwrap();
+        self.write_buffer().write().unwrap().copy_from_slice(&published);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Resolution;
+    use crate::buffer::FrameBuffer;
+    use crate::tracer::colour::Colour;
+    use crate::tracer::tile::Tile;
+
+    #[test]
+    fn test_publish_keeps_earlier_tiles() {
+        let frame = FrameBuffer::new(Resolution { width: 4, height: 2 });
+        let left = Tile { x: 0, y: 0, width: 2, height: 2 };
+        let right = Tile { x: 2, y: 0, width: 2, height: 2 };
+
+        frame.write_tile(&left, &[1; 16], &[Colour::white(); 4], &[]);
+        frame.publish();
+        assert_eq!(frame.read_buffer().read().unwrap()[0], 1);
+        assert_eq!(frame.read_buffer().read().unwrap()[8], 255);
+
+        frame.write_tile(&right, &[2; 16], &[Colour::new(2.0, 2.0, 2.0); 4], &[]);
+        frame.publish();
+        let published = frame.read_buffer().read().unwrap();
+        assert_eq!(published[0], 1);
+        assert_eq!(published[8], 2);
+        let radiance = frame.radiance().read().unwrap();
+        assert_eq!((radiance[0], radiance[2], radiance[6]), (Colour::white(), Colour::new(2.0, 2.0, 2.0), Colour::new(2.0, 2.0, 2.0)));
+    }
+}
@@ -1,12 +1,31 @@
 use getopts::Options;
 use std::string::ToString;
 use crate::Resolution;
+use crate::output::ExrPrecision;
+use crate::tracer::aov::{self, Aov};
+use crate::tracer::colour::tonemap::ToneMap;
+use crate::tracer::sampler::SamplerType;
 
 pub struct Config {
     pub filename: String,
     pub threads: u8,
     pub resolution: Resolution,
-    pub samples: u8
+    pub samples: u8,
+    pub output: Option<String>,
+    /// Override the scene file's tone mapping and exposure when given
+    pub tone_map: Option<ToneMap>,
+    pub exposure: Option<f64>,
+    /// Overrides the scene file's integrator when given
+    pub integrator: Option<String>,
+    /// Show which pixels have NaN or infinite samples
+    pub flag_nan: bool,
+    /// Override the scene file's sampler and seed when given
+    pub sampler: Option<SamplerType>,
+    pub seed: Option<u64>,
+    pub exr_precision: ExrPrecision,
+    pub aovs: Vec<Aov>,
+    /// Write AOVs as their own files even when the output is an EXR that could hold them as layers
+    pub aov_files: bool
 }
 
 pub fn parse_args(args: Vec<String>) -> Config {
@@ -17,10 +36,20 @@ pub fn parse_args(args: Vec<String>) -> Config {
     opts.optopt("", "help", "window height", "window height");
     opts.optopt("w", "width", "window width", "window width");
     opts.optopt("s", "samples", "pixel super samples", "pixel super samples");
+    opts.optopt("", "tonemap", "tone mapping operator: clamp, reinhard, extended-reinhard, aces or uncharted2", "operator");
+    opts.optopt("", "exposure", "exposure adjustment in stops", "stops");
+    opts.optopt("", "integrator", "how light is worked out: phong, path, ao for a clay render, or debug views normals, depth, uv, heatmap or bvh-heatmap", "integrator");
+    opts.optopt("", "sampler", "where samples go in each pixel: independent, stratified, halton or sobol", "sampler");
+    opts.optopt("", "seed", "renders with the same seed come out the same", "seed");
+    opts.optflag("", "flag-nan", "grey out the image and show pixels with NaN or infinite samples in magenta");
+    opts.optopt("o", "output", "render without a window and write the image to a .png, .ppm, .pfm, .exr or .hdr file", "output file");
+    opts.optopt("", "aov", "comma separated passes to write with --output: depth, normal, albedo, object_id, material_id, direct, ambient", "passes");
+    opts.optflag("", "aov-files", "write AOVs as separate files even when the output is an .exr");
+    opts.optflag("", "exr-half", "write .exr files with half floats rather than full floats");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+        Err(f) => panic!("{}", f),
     };
 
     if matches.opt_present("help") {
@@ -35,11 +64,32 @@ pub fn parse_args(args: Vec<String>) -> Config {
     let file: String = matches
         .opt_get_default("f", "scene.yml".to_string())
         .unwrap();
+    let output: Option<String> = matches.opt_str("o");
+    let tone_map: Option<ToneMap> = matches.opt_get("tonemap").unwrap_or_else(|err| panic!("{}", err));
+    let exposure: Option<f64> = matches.opt_get("exposure").unwrap();
+    let integrator: Option<String> = matches.opt_str("integrator");
+    let sampler: Option<SamplerType> = matches.opt_get("sampler").unwrap_or_else(|err| panic!("{}", err));
+    let seed: Option<u64> = matches.opt_get("seed").unwrap();
+    let aovs: Vec<Aov> = match matches.opt_str("aov") {
+        Some(names) => aov::parse_list(&names).unwrap_or_else(|err| panic!("{}", err)),
+        None => vec![]
+    };
+    let exr_precision = if matches.opt_present("exr-half") { ExrPrecision::Half } else { ExrPrecision::Float };
     Config {
         filename: file.clone(),
         threads,
         resolution: Resolution { width, height },
-        samples
+        samples,
+        output,
+        tone_map,
+        exposure,
+        integrator,
+        flag_nan: matches.opt_present("flag-nan"),
+        sampler,
+        seed,
+        exr_precision,
+        aovs,
+        aov_files: matches.opt_present("aov-files")
     }
 }
 
@@ -2,6 +2,8 @@ pub mod tracer;
 pub mod config;
 pub mod window;
 pub mod buffer;
+pub mod output;
+pub mod scene;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Resolution {
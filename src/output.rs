@@ -0,0 +1,533 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Resolution;
+use crate::buffer::FrameBuffer;
+use crate::tracer::aov::Aov;
+use crate::tracer::colour::Colour;
+use crate::tracer::colour::tonemap::DisplayTransform;
+
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+//Single part scanline file, no flags set
+const EXR_VERSION: u32 = 2;
+//Runs shorter than this are cheaper to store as literals in Radiance RLE
+const HDR_MIN_RUN: usize = 4;
+const RGBE_MAX: f64 = 1e38;
+
+/// Image formats a render can be written to, picked from the output file's extension. PNG and
+/// PPM get the tone mapped display pixels, the rest the linear radiance.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    Ppm,
+    Pfm,
+    Exr,
+    Hdr,
+}
+
+/// Channel type for OpenEXR output.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+impl ExrPrecision {
+    fn pixel_type(self) -> i32 {
+        match self {
+            ExrPrecision::Half => 1,
+            ExrPrecision::Float => 2,
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            ExrPrecision::Half => 2,
+            ExrPrecision::Float => 4,
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> io::Result<OutputFormat> {
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") => Ok(OutputFormat::Png),
+            Some("ppm") => Ok(OutputFormat::Ppm),
+            Some("pfm") => Ok(OutputFormat::Pfm),
+            Some("exr") => Ok(OutputFormat::Exr),
+            Some("hdr") => Ok(OutputFormat::Hdr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can't tell the image format of {}, expected a .png, .ppm, .pfm, .exr or .hdr file",
+                        path.display()),
+            ))
+        }
+    }
+}
+
+/// Writes the published frame to `path` in the format matching its extension. With `aov_layers`
+/// the frame's AOVs go in as extra layers, which only OpenEXR can hold.
+pub fn write_image(path: &Path, frame: &FrameBuffer, exr_precision: ExrPrecision, aov_layers: bool)
+    -> io::Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    if aov_layers && format != OutputFormat::Exr && !frame.aovs().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Only .exr files can hold AOV layers, can't write them to {}", path.display()),
+        ));
+    }
+    let res = frame.resolution();
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        OutputFormat::Png => write_png(&mut writer, &res, &frame.read_buffer().read().unwrap())?,
+        OutputFormat::Ppm => write_ppm(&mut writer, &res, &frame.read_buffer().read().unwrap())?,
+        OutputFormat::Pfm => write_pfm(&mut writer, &res, &frame.radiance().read().unwrap())?,
+        OutputFormat::Exr => {
+            let aov_guards: Vec<_> = if aov_layers {
+                frame.aovs().iter().map(|&aov| (aov, frame.aov(aov).unwrap().read().unwrap())).collect()
+            } else {
+                vec![]
+            };
+            let aovs: Vec<(Aov, &[Colour])> = aov_guards.iter().map(|(aov, values)| (*aov, &values[..])).collect();
+            write_exr(&mut writer, &res, &frame.radiance().read().unwrap(), &aovs, exr_precision)?
+        }
+        OutputFormat::Hdr => write_hdr(&mut writer, &res, &frame.radiance().read().unwrap())?,
+    }
+    writer.flush()
+}
+
+/// Writes each of the frame's AOVs to its own file next to `path`, `render.exr` gets
+/// `render.depth.exr` and so on. 8 bit formats get a viewable version, the float ones the raw
+/// values with single channel AOVs repeated across R, G and B (except in OpenEXR).
+pub fn write_aov_images(path: &Path, frame: &FrameBuffer, exr_precision: ExrPrecision, display: &DisplayTransform)
+    -> io::Result<Vec<PathBuf>> {
+    let format = OutputFormat::from_path(path)?;
+    let res = frame.resolution();
+    let mut written = vec![];
+    for &aov in frame.aovs() {
+        let aov_path = aov_path(path, aov);
+        let values = frame.aov(aov).unwrap().read().unwrap();
+        let grey: Vec<Colour>;
+        let float_values: &[Colour] = if aov.channels().len() == 1 && format != OutputFormat::Exr {
+            grey = values.iter().map(|value| Colour::new(value.r, value.r, value.r)).collect();
+            &grey
+        } else {
+            &values
+        };
+        let mut writer = BufWriter::new(File::create(&aov_path)?);
+        match format {
+            OutputFormat::Png | OutputFormat::Ppm => {
+                let rgba: Vec<u8> = values.iter()
+                    .flat_map(|value| {
+                        let pixel = aov.to_display(*value, display);
+                        [pixel.r, pixel.g, pixel.b, 255]
+                    })
+                    .collect();
+                if format == OutputFormat::Png {
+                    write_png(&mut writer, &res, &rgba)?;
+                } else {
+                    write_ppm(&mut writer, &res, &rgba)?;
+                }
+            }
+            OutputFormat::Pfm => write_pfm(&mut writer, &res, float_values)?,
+            OutputFormat::Exr => write_aov_exr(&mut writer, &res, aov, &values, exr_precision)?,
+            OutputFormat::Hdr => write_hdr(&mut writer, &res, float_values)?,
+        }
+        writer.flush()?;
+        written.push(aov_path);
+    }
+    Ok(written)
+}
+
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("render");
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    path.with_file_name(format!("{}.{}.{}", stem, aov, extension))
+}
+
+pub fn write_png<W: Write>(writer: W, res: &Resolution, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, res.width as u32, res.height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(rgba)?;
+    Ok(())
+}
+
+/// Binary (P6) portable pixmap, alpha is dropped.
+pub fn write_ppm<W: Write>(mut writer: W, res: &Resolution, rgba: &[u8]) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", res.width, res.height)?;
+    for pixel in rgba.chunks(4) {
+        writer.write_all(&pixel[0..3])?;
+    }
+    Ok(())
+}
+
+/// Colour portable float map. The negative scale marks the data as little endian and the rows
+/// are stored bottom to top.
+pub fn write_pfm<W: Write>(mut writer: W, res: &Resolution, radiance: &[Colour]) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", res.width, res.height)?;
+    for row in radiance.chunks(res.width as usize).rev() {
+        for pixel in row {
+            for channel in &[pixel.r, pixel.g, pixel.b] {
+                writer.write_all(&(*channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Uncompressed single part scanline OpenEXR with R, G and B channels, plus a layer for each of
+/// `aovs` with channels named like `normal.X`.
+pub fn write_exr<W: Write>(writer: W, res: &Resolution, radiance: &[Colour], aovs: &[(Aov, &[Colour])],
+                           precision: ExrPrecision) -> io::Result<()> {
+    let mut planes: Vec<ExrPlane> = ["R", "G", "B"].iter().enumerate()
+        .map(|(index, name)| (name.to_string(), radiance, index))
+        .collect();
+    for (aov, values) in aovs {
+        for (index, name) in aov.channels().iter().enumerate() {
+            planes.push((format!("{}.{}", aov, name), values, index));
+        }
+    }
+    write_exr_planes(writer, res, planes, precision)
+}
+
+/// OpenEXR holding just one AOV, with its channels unprefixed.
+pub fn write_aov_exr<W: Write>(writer: W, res: &Resolution, aov: Aov, values: &[Colour], precision: ExrPrecision)
+    -> io::Result<()> {
+    let planes = aov.channels().iter().enumerate()
+        .map(|(index, name)| (name.to_string(), values, index))
+        .collect();
+    write_exr_planes(writer, res, planes, precision)
+}
+
+//A channel's name, the colours it's taken from and which of their channels to use
+type ExrPlane<'a> = (String, &'a [Colour], usize);
+
+fn write_exr_planes<W: Write>(mut writer: W, res: &Resolution, mut planes: Vec<ExrPlane>, precision: ExrPrecision)
+    -> io::Result<()> {
+    let (width, height) = (res.width as i32, res.height as i32);
+    let mut header = Vec::new();
+    header.extend_from_slice(&EXR_MAGIC);
+    header.extend_from_slice(&EXR_VERSION.to_le_bytes());
+
+    //channels have to be listed, and stored, in alphabetical order
+    planes.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+    let mut channels = Vec::new();
+    for (name, _, _) in &planes {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&precision.pixel_type().to_le_bytes());
+        //pLinear and three reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, width - 1, height - 1].iter().flat_map(|value: &i32| value.to_le_bytes()).collect();
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+    writer.write_all(&header)?;
+
+    //one line per chunk, each chunk is its y and byte count then the line's values channel by channel
+    let width = res.width as usize;
+    let line_bytes = width * planes.len() * precision.bytes();
+    let chunk_bytes = 8 + line_bytes;
+    let first_chunk = header.len() + res.height as usize * 8;
+    for y in 0..res.height as usize {
+        writer.write_all(&((first_chunk + y * chunk_bytes) as u64).to_le_bytes())?;
+    }
+    for y in 0..res.height as usize {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_bytes as i32).to_le_bytes())?;
+        for (_, values, index) in &planes {
+            for pixel in &values[y * width..(y + 1) * width] {
+                let value = [pixel.r, pixel.g, pixel.b][*index] as f32;
+                match precision {
+                    ExrPrecision::Half => writer.write_all(&to_half(value).to_le_bytes())?,
+                    ExrPrecision::Float => writer.write_all(&value.to_le_bytes())?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Rounds to the nearest IEEE 754 half, overflowing to infinity.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        //infinity stays infinity, NaN keeps a mantissa bit set
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        //subnormal, shift the mantissa with its implicit leading bit down into place
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        return sign | round_shifted(mantissa, shift) as u16;
+    }
+    //rounding can carry into the exponent, which is still the right answer
+    sign | (((half_exponent as u32) << 10) + round_shifted(mantissa, 13)) as u16
+}
+
+/// `value >> shift` rounded to nearest, ties to even.
+fn round_shifted(value: u32, shift: u32) -> u32 {
+    let shifted = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    if remainder > halfway || (remainder == halfway && shifted & 1 == 1) {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+/// Radiance RGBE with run length encoded scanlines.
+pub fn write_hdr<W: Write>(mut writer: W, res: &Resolution, radiance: &[Colour]) -> io::Result<()> {
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", res.height, res.width)?;
+    let width = res.width as usize;
+    for row in radiance.chunks(width) {
+        let rgbe: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+        //the run length scheme only works for these widths, others are written flat
+        if !(8..=0x7fff).contains(&width) {
+            for pixel in &rgbe {
+                writer.write_all(pixel)?;
+            }
+            continue;
+        }
+        writer.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for component in 0..4 {
+            let values: Vec<u8> = rgbe.iter().map(|pixel| pixel[component]).collect();
+            write_hdr_rle(&mut writer, &values)?;
+        }
+    }
+    Ok(())
+}
+
+/// Shared exponent encoding, the mantissas are each channel over 2^(exponent - 128) times 256.
+fn to_rgbe(colour: &Colour) -> [u8; 4] {
+    //min would turn a NaN into the clamp value, so NaNs have to be caught first
+    if colour.r.is_nan() || colour.g.is_nan() || colour.b.is_nan() {
+        return [0, 0, 0, 0];
+    }
+    //infinities (like the depth of the background) saturate at the largest exponent
+    let colour = Colour::new(colour.r.min(RGBE_MAX), colour.g.min(RGBE_MAX), colour.b.min(RGBE_MAX));
+    let brightest = colour.max_channel();
+    if brightest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let mantissa = |channel: f64| (channel.max(0.0) * scale).min(255.0) as u8;
+    [mantissa(colour.r), mantissa(colour.g), mantissa(colour.b), (exponent + 128) as u8]
+}
+
+/// One component of a scanline as runs (a count over 128 then the repeated byte) and literals
+/// (a count up to 128 then that many bytes).
+fn write_hdr_rle<W: Write>(writer: &mut W, values: &[u8]) -> io::Result<()> {
+    let run_length = |start: usize, limit: usize| {
+        values[start..].iter().take(limit).take_while(|&&value| value == values[start]).count()
+    };
+    let mut i = 0;
+    while i < values.len() {
+        let run = run_length(i, 127);
+        if run >= HDR_MIN_RUN {
+            writer.write_all(&[128 + run as u8, values[i]])?;
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < values.len() && i - start < 128 && run_length(i, HDR_MIN_RUN) < HDR_MIN_RUN {
+            i += 1;
+        }
+        writer.write_all(&[(i - start) as u8])?;
+        writer.write_all(&values[start..i])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::Resolution;
+    use crate::output::{ExrPrecision, OutputFormat, aov_path, to_half, to_rgbe, write_exr, write_hdr, write_hdr_rle,
+                        write_pfm, write_ppm};
+    use crate::tracer::aov::Aov;
+    use crate::tracer::colour::Colour;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(OutputFormat::from_path(Path::new("render.PNG")).unwrap(), OutputFormat::Png);
+        assert_eq!(OutputFormat::from_path(Path::new("out/render.ppm")).unwrap(), OutputFormat::Ppm);
+        assert_eq!(OutputFormat::from_path(Path::new("render.pfm")).unwrap(), OutputFormat::Pfm);
+        assert_eq!(OutputFormat::from_path(Path::new("render.exr")).unwrap(), OutputFormat::Exr);
+        assert_eq!(OutputFormat::from_path(Path::new("render.hdr")).unwrap(), OutputFormat::Hdr);
+        assert!(OutputFormat::from_path(Path::new("render.jpg")).is_err());
+        assert!(OutputFormat::from_path(Path::new("render")).is_err());
+    }
+
+    #[test]
+    fn test_ppm_and_pfm_layout() {
+        let res = Resolution { width: 1, height: 2 };
+        let rgba = [255, 0, 51, 255, 0, 255, 0, 255];
+
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, &res, &rgba).unwrap();
+        assert_eq!(ppm, b"P6\n1 2\n255\n\xff\x00\x33\x00\xff\x00".to_vec());
+
+        let mut pfm = Vec::new();
+        write_pfm(&mut pfm, &res, &[Colour::new(1.0, 0.0, 0.2), Colour::new(0.0, 2.5, 0.0)]).unwrap();
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        //bottom row first, and not clamped
+        assert_eq!(&pfm[header.len()..header.len() + 4], &0.0f32.to_le_bytes());
+        assert_eq!(&pfm[header.len() + 4..header.len() + 8], &2.5f32.to_le_bytes());
+        assert_eq!(pfm.len(), header.len() + 2 * 3 * 4);
+    }
+
+    #[test]
+    fn test_half_conversion() {
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(0.1), 0x2e66);
+        assert_eq!(to_half(65504.0), 0x7bff);
+        assert_eq!(to_half(1e6), 0x7c00);
+        assert_eq!(to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(to_half(2f32.powi(-26)), 0x0000);
+        assert_eq!(to_half(f32::INFINITY), 0x7c00);
+        assert!(to_half(f32::NAN) & 0x3ff != 0);
+    }
+
+    #[test]
+    fn test_exr_layout() {
+        let res = Resolution { width: 3, height: 2 };
+        let radiance = vec![Colour::new(1.0, 0.5, 4.0); 6];
+        for &(precision, bytes) in &[(ExrPrecision::Half, 2), (ExrPrecision::Float, 4)] {
+            let mut exr = Vec::new();
+            write_exr(&mut exr, &res, &radiance, &[], precision).unwrap();
+            assert_eq!(&exr[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+            let line_bytes = 3 * 3 * bytes;
+            let first_chunk = exr.len() - 2 * (8 + line_bytes);
+            let offsets: Vec<u64> = exr[first_chunk - 16..first_chunk].chunks(8)
+                .map(|offset| u64::from_le_bytes([offset[0], offset[1], offset[2], offset[3], offset[4], offset[5], offset[6], offset[7]]))
+                .collect();
+            assert_eq!(offsets, vec![first_chunk as u64, (first_chunk + 8 + line_bytes) as u64]);
+            let second_line = first_chunk + 8 + line_bytes;
+            assert_eq!(&exr[second_line..second_line + 8], &[1, 0, 0, 0, line_bytes as u8, 0, 0, 0]);
+            //blue comes first
+            let blue = &exr[second_line + 8..second_line + 8 + bytes];
+            match precision {
+                ExrPrecision::Half => assert_eq!(blue, &to_half(4.0).to_le_bytes()),
+                ExrPrecision::Float => assert_eq!(blue, &4.0f32.to_le_bytes()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exr_aov_layers() {
+        let res = Resolution { width: 2, height: 1 };
+        let radiance = vec![Colour::white(); 2];
+        let depth = vec![Colour::new(3.0, 0.0, 0.0), Colour::new(f64::INFINITY, 0.0, 0.0)];
+        let normal = vec![Colour::new(0.0, 0.0, -1.0); 2];
+        let mut exr = Vec::new();
+        write_exr(&mut exr, &res, &radiance, &[(Aov::Normal, &normal), (Aov::Depth, &depth)], ExrPrecision::Float)
+            .unwrap();
+        let expected = ["B", "G", "R", "depth.Z", "normal.X", "normal.Y", "normal.Z"];
+        let list_start = exr.windows(7).position(|window| window == b"chlist\0").unwrap() + 7 + 4;
+        let mut names = vec![];
+        let mut at = list_start;
+        while exr[at] != 0 {
+            let end = at + exr[at..].iter().position(|&byte| byte == 0).unwrap();
+            names.push(String::from_utf8(exr[at..end].to_vec()).unwrap());
+            at = end + 1 + 16;
+        }
+        assert_eq!(names, expected);
+        //a single line, stored channel by channel in the same order
+        let line = &exr[exr.len() - 7 * 2 * 4..];
+        assert_eq!(&line[3 * 8..3 * 8 + 4], &3.0f32.to_le_bytes());
+        assert_eq!(&line[3 * 8 + 4..4 * 8], &f32::INFINITY.to_le_bytes());
+        assert_eq!(&line[6 * 8..6 * 8 + 4], &(-1.0f32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_aov_paths() {
+        assert_eq!(aov_path(Path::new("out/render.png"), Aov::ObjectId), Path::new("out/render.object_id.png"));
+        assert_eq!(aov_path(Path::new("render.exr"), Aov::Depth), Path::new("render.depth.exr"));
+    }
+
+    fn decode_hdr_rle(mut data: &[u8], len: usize) -> (Vec<u8>, &[u8]) {
+        let mut values = vec![];
+        while values.len() < len {
+            if data[0] > 128 {
+                values.extend(std::iter::repeat_n(data[1], data[0] as usize - 128));
+                data = &data[2..];
+            } else {
+                let count = data[0] as usize;
+                values.extend_from_slice(&data[1..1 + count]);
+                data = &data[1 + count..];
+            }
+        }
+        (values, data)
+    }
+
+    #[test]
+    fn test_hdr_rle_round_trip() {
+        assert_eq!(to_rgbe(&Colour::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&Colour::black()), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(&Colour::new(f64::INFINITY, 0.0, 0.0))[3], 255);
+        assert_eq!(to_rgbe(&Colour::new(f64::NAN, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(&Colour::new(1.0, f64::NAN, 1.0)), [0, 0, 0, 0]);
+
+        let mut values: Vec<u8> = (0..150).map(|i| (i % 7) as u8).collect();
+        values.extend(std::iter::repeat_n(9, 300));
+        values.extend(&[1, 1, 1, 2, 2, 3]);
+        let mut encoded = Vec::new();
+        write_hdr_rle(&mut encoded, &values).unwrap();
+        assert!(encoded.len() < values.len());
+        let (decoded, rest) = decode_hdr_rle(&encoded, values.len());
+        assert_eq!(decoded, values);
+        assert!(rest.is_empty());
+
+        let res = Resolution { width: 10, height: 1 };
+        let mut hdr = Vec::new();
+        write_hdr(&mut hdr, &res, &[Colour::new(1.0, 0.5, 0.0); 10]).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 10\n";
+        assert_eq!(&hdr[..header.len()], header);
+        assert_eq!(&hdr[header.len()..header.len() + 4], &[2, 2, 0, 10]);
+        let (red, rest) = decode_hdr_rle(&hdr[header.len() + 4..], 10);
+        assert_eq!(red, vec![128; 10]);
+        assert_eq!(rest.len(), 3 * 2);
+    }
+}
@@ -0,0 +1,1062 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use nalgebra::{Matrix4, Rotation3, Vector2, Vector3};
+use serde::Deserialize;
+use serde::de::{DeserializeSeed, Deserializer};
+use serde_path_to_error::Track;
+
+use crate::tracer::{Camera, SceneState, DEFAULT_MAX_DEPTH};
+use crate::tracer::colour::{Colour, Material, RGB, DEFAULT_IOR};
+use crate::tracer::colour::tonemap::{DisplayTransform, ToneMap};
+use crate::tracer::geom::Drawable;
+use crate::tracer::geom::mesh::TriangleMesh;
+use crate::tracer::geom::plane::Plane;
+use crate::tracer::geom::sphere::Sphere;
+use crate::tracer::geom::triangle::Triangle;
+use crate::tracer::integrator::IntegratorRegistry;
+use crate::tracer::occlusion::{AmbientOcclusion, DEFAULT_AO_DISTANCE, DEFAULT_AO_SAMPLES};
+use crate::tracer::sampler::SamplerType;
+use crate::tracer::lens::{Aperture, ThinLens};
+use crate::tracer::light::{AreaLight, AreaShape, DirectionalLight, Light, PointLight, SpotLight};
+use crate::tracer::projection::{Equirectangular, Fisheye, Orthographic, Perspective, Projection, ProjectionType};
+
+pub mod obj;
+
+/// Something wrong with a scene file, `path` points at the offending YAML key when there is one.
+#[derive(Debug)]
+pub struct SceneError {
+    pub file: String,
+    /// Line and column in the file, counting from 1, for errors found while reading the YAML.
+    pub location: Option<(usize, usize)>,
+    pub path: Option<String>,
+    pub reason: String,
+}
+
+impl SceneError {
+    fn at(file: &str, path: &str, reason: &str) -> SceneError {
+        SceneError {
+            file: file.to_string(),
+            location: None,
+            path: Some(path.to_string()),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some((line, column)) = self.location {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        match &self.path {
+            Some(path) => write!(f, ": {}: {}", path, self.reason),
+            None => write!(f, ": {}", self.reason),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Xyz {
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    #[serde(default)]
+    pub z: f64,
+}
+
+impl Xyz {
+    fn to_vector(self) -> Vector3<f64> {
+        Vector3::new(self.x, self.y, self.z)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rgb {
+    #[serde(default)]
+    pub r: u8,
+    #[serde(default)]
+    pub g: u8,
+    #[serde(default)]
+    pub b: u8,
+}
+
+impl Rgb {
+    fn to_colour(self) -> Colour {
+        Colour::from_srgb(RGB::new(self.r, self.g, self.b))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraEntry,
+    #[serde(default)]
+    pub spheres: Vec<SphereEntry>,
+    #[serde(default)]
+    pub planes: Vec<PlaneEntry>,
+    #[serde(default)]
+    pub triangles: Vec<TriangleEntry>,
+    #[serde(default)]
+    pub meshes: Vec<MeshEntry>,
+    #[serde(default)]
+    pub point_lights: Vec<PointLightEntry>,
+    #[serde(default)]
+    pub directional_lights: Vec<DirectionalLightEntry>,
+    #[serde(default)]
+    pub spot_lights: Vec<SpotLightEntry>,
+    #[serde(default)]
+    pub area_lights: Vec<AreaLightEntry>,
+    #[serde(default = "default_ambient")]
+    pub ambient: f64,
+    /// Darkens `ambient` in crevices when given
+    #[serde(default)]
+    pub ambient_occlusion: Option<AmbientOcclusionEntry>,
+    #[serde(default)]
+    pub background: Rgb,
+    /// Reflection and refraction bounces to follow with Phong shading, and bounces of any kind
+    /// with path tracing
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// One of the `ToneMap` names, clamp when not given
+    #[serde(default)]
+    pub tone_map: Option<String>,
+    /// In stops
+    #[serde(default)]
+    pub exposure: f64,
+    #[serde(default = "default_white_point")]
+    pub white_point: f64,
+    /// Name of an integrator in the registry the scene is loaded with, phong when not given
+    #[serde(default)]
+    pub integrator: Option<String>,
+    /// One of the `SamplerType` names, stratified when not given
+    #[serde(default)]
+    pub sampler: Option<String>,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraEntry {
+    #[serde(default = "origin")]
+    pub pos: Xyz,
+    /// Straight ahead along z unless `look_at` is given instead
+    #[serde(default)]
+    pub dir: Option<Xyz>,
+    /// A point to aim at, can't be combined with `dir`
+    #[serde(default)]
+    pub look_at: Option<Xyz>,
+    #[serde(default = "world_up")]
+    pub up: Xyz,
+    /// Degrees about the view direction
+    #[serde(default)]
+    pub roll: f64,
+    /// One of the `ProjectionType` names, perspective when not given
+    #[serde(rename = "type", default)]
+    pub projection: Option<String>,
+    /// Degrees across the image, or across the circle for a fisheye. Defaults to 90, or 180 for a
+    /// fisheye, and can't be given for an equirectangular camera which always sees everything
+    #[serde(default)]
+    pub fov: Option<f64>,
+    /// World units across an orthographic view, by default what a 90 degree perspective camera
+    /// sees at the end of `dir`
+    #[serde(default)]
+    pub width: Option<f64>,
+    /// Lens radius for depth of field, a pinhole camera when neither this nor `f_stop` is given
+    #[serde(default)]
+    pub aperture: Option<f64>,
+    /// Sets the aperture from a full frame camera's f-stop, with scene units in metres
+    #[serde(default)]
+    pub f_stop: Option<f64>,
+    /// Distance that's in focus, the length of `dir` or the distance to `look_at` when not given
+    #[serde(default)]
+    pub focus_dist: Option<f64>,
+    /// Aperture blades for polygonal bokeh, a round aperture when not given
+    #[serde(default)]
+    pub blades: Option<u32>,
+}
+
+impl CameraEntry {
+    fn to_projection(&self, file: &str, view_dist: f64) -> Result<(Box<dyn Projection>, f64), SceneError> {
+        let projection = match &self.projection {
+            Some(name) => name.parse::<ProjectionType>().map_err(|reason| SceneError::at(file, "camera.type", &reason))?,
+            None => ProjectionType::Perspective
+        };
+        if self.width.is_some() && projection != ProjectionType::Orthographic {
+            return Err(SceneError::at(file, "camera.width", "width only applies to orthographic cameras"));
+        }
+        let fov = match (projection, self.fov) {
+            (ProjectionType::Equirectangular, Some(_)) => {
+                return Err(SceneError::at(file, "camera.fov", "an equirectangular camera always sees all the way round"));
+            }
+            (ProjectionType::Fisheye, fov) => {
+                let fov = fov.unwrap_or(180.0);
+                if fov <= 0.0 || fov > 360.0 {
+                    return Err(SceneError::at(file, "camera.fov", "fisheye fov must be between 0 and 360 degrees"));
+                }
+                fov
+            }
+            (_, fov) => {
+                let fov = fov.unwrap_or_else(default_fov);
+                if fov <= 0.0 || fov >= 180.0 {
+                    return Err(SceneError::at(file, "camera.fov", "fov must be between 0 and 180 degrees"));
+                }
+                fov
+            }
+        };
+        let projection: Box<dyn Projection> = match projection {
+            ProjectionType::Perspective => Box::new(Perspective),
+            ProjectionType::Orthographic => {
+                let width = self.width.unwrap_or_else(|| 2.0 * view_dist * (fov.to_radians() / 2.0).tan());
+                if width <= 0.0 {
+                    return Err(SceneError::at(file, "camera.width", "width must be positive"));
+                }
+                Box::new(Orthographic { width })
+            }
+            ProjectionType::Fisheye => Box::new(Fisheye { fov }),
+            ProjectionType::Equirectangular => Box::new(Equirectangular),
+        };
+        Ok((projection, fov))
+    }
+
+    fn to_lens(&self, file: &str, view_dist: f64) -> Result<Option<ThinLens>, SceneError> {
+        let radius = match (self.aperture, self.f_stop) {
+            (Some(_), Some(_)) => return Err(SceneError::at(file, "camera", "give either aperture or f_stop, not both")),
+            (Some(radius), None) if radius < 0.0 => {
+                return Err(SceneError::at(file, "camera.aperture", "aperture can't be negative"));
+            }
+            (Some(radius), None) => radius,
+            (None, Some(f_stop)) if f_stop <= 0.0 => {
+                return Err(SceneError::at(file, "camera.f_stop", "f-stop must be positive"));
+            }
+            (None, Some(f_stop)) => ThinLens::radius_for_f_stop(f_stop, self.fov.unwrap_or_else(default_fov)),
+            (None, None) => 0.0,
+        };
+        let focus_dist = self.focus_dist.unwrap_or(view_dist);
+        if focus_dist <= 0.0 {
+            return Err(SceneError::at(file, "camera.focus_dist", "focus distance must be positive"));
+        }
+        let aperture = match self.blades {
+            Some(blades) if blades < 3 => {
+                return Err(SceneError::at(file, "camera.blades", "an aperture needs at least 3 blades"));
+            }
+            Some(blades) => Aperture::Polygon(blades),
+            None => Aperture::Disk,
+        };
+        if radius == 0.0 {
+            return Ok(None);
+        }
+        Ok(Some(ThinLens { radius, focus_dist, aperture }))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SphereEntry {
+    pub pos: Xyz,
+    pub radius: f64,
+    #[serde(default)]
+    pub material: MaterialEntry,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneEntry {
+    #[serde(default = "origin")]
+    pub pos: Xyz,
+    pub norm: Xyz,
+    #[serde(default)]
+    pub material: MaterialEntry,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TriangleEntry {
+    pub a: Xyz,
+    pub b: Xyz,
+    pub c: Xyz,
+    #[serde(default)]
+    pub material: MaterialEntry,
+}
+
+/// Either an OBJ `file`, relative to the scene file, or an inline mesh where each index triple
+/// picks three vertices (and their normals and UVs) to make a triangle.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshEntry {
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub vertices: Option<Vec<[f64; 3]>>,
+    #[serde(default)]
+    pub indices: Option<Vec<[usize; 3]>>,
+    #[serde(default)]
+    pub normals: Option<Vec<[f64; 3]>>,
+    #[serde(default)]
+    pub uvs: Option<Vec<[f64; 2]>>,
+    /// Overrides the OBJ's own materials when given
+    #[serde(default)]
+    pub material: Option<MaterialEntry>,
+    #[serde(default)]
+    pub transform: Option<TransformEntry>,
+}
+
+/// Scales, then rotates about x, y and z in turn (in degrees), then translates.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformEntry {
+    #[serde(default = "origin")]
+    pub translate: Xyz,
+    #[serde(default = "origin")]
+    pub rotate: Xyz,
+    #[serde(default = "unit_scale")]
+    pub scale: ScaleEntry,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ScaleEntry {
+    Uniform(f64),
+    PerAxis([f64; 3]),
+}
+
+impl TransformEntry {
+    fn to_matrix(&self, file: &str, path: &str) -> Result<Matrix4<f64>, SceneError> {
+        let scale = match self.scale {
+            ScaleEntry::Uniform(scale) => Vector3::new(scale, scale, scale),
+            ScaleEntry::PerAxis([x, y, z]) => Vector3::new(x, y, z),
+        };
+        if scale.iter().any(|&axis| axis == 0.0) {
+            return Err(SceneError::at(file, &format!("{}.scale", path), "scale can't be zero"));
+        }
+        let rotation = Rotation3::from_euler_angles(
+            self.rotate.x.to_radians(),
+            self.rotate.y.to_radians(),
+            self.rotate.z.to_radians(),
+        );
+        Ok(Matrix4::new_translation(&self.translate.to_vector())
+            * rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&scale))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightEntry {
+    pub pos: Xyz,
+    #[serde(default = "white")]
+    pub colour: Rgb,
+    #[serde(default = "one")]
+    pub intensity: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightEntry {
+    /// The way the light shines, from the sky down into the scene
+    pub dir: Xyz,
+    #[serde(default = "white")]
+    pub colour: Rgb,
+    #[serde(default = "one")]
+    pub intensity: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpotLightEntry {
+    pub pos: Xyz,
+    /// The way the middle of the cone points
+    pub dir: Xyz,
+    /// Degrees from the middle of the cone out to where the light starts fading
+    #[serde(default = "default_spot_inner")]
+    pub inner_angle: f64,
+    /// Degrees from the middle of the cone out to where the light has faded to nothing
+    #[serde(default = "default_spot_outer")]
+    pub outer_angle: f64,
+    #[serde(default = "white")]
+    pub colour: Rgb,
+    #[serde(default = "one")]
+    pub intensity: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AreaLightEntry {
+    /// rectangle, disk or sphere
+    pub shape: String,
+    /// The middle of the shape
+    pub pos: Xyz,
+    /// A rectangle's edges, at right angles to each other, it shines towards `u` cross `v`
+    #[serde(default)]
+    pub u: Option<Xyz>,
+    #[serde(default)]
+    pub v: Option<Xyz>,
+    /// The way a disk shines
+    #[serde(default)]
+    pub normal: Option<Xyz>,
+    /// For disks and spheres
+    #[serde(default)]
+    pub radius: Option<f64>,
+    #[serde(default = "white")]
+    pub colour: Rgb,
+    #[serde(default = "one")]
+    pub intensity: f64,
+    /// Shadow rays per shading point, more gives smoother penumbrae
+    #[serde(default = "default_area_samples")]
+    pub samples: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmbientOcclusionEntry {
+    /// Rays per shading point, more gives smoother shading
+    #[serde(default = "default_ao_samples")]
+    pub samples: u32,
+    /// How far away something can be and still occlude
+    #[serde(default = "default_ao_distance")]
+    pub distance: f64,
+}
+
+impl AmbientOcclusionEntry {
+    fn to_occlusion(&self, file: &str) -> Result<AmbientOcclusion, SceneError> {
+        if self.samples == 0 {
+            return Err(SceneError::at(file, "ambient_occlusion.samples", "ambient occlusion needs at least one sample"));
+        }
+        if self.distance <= 0.0 {
+            return Err(SceneError::at(file, "ambient_occlusion.distance", "distance must be positive"));
+        }
+        Ok(AmbientOcclusion { samples: self.samples, max_dist: self.distance })
+    }
+}
+
+impl AreaLightEntry {
+    fn to_light(&self, file: &str, path: &str) -> Result<AreaLight, SceneError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+        let missing = |name: &str| SceneError::at(file, &field(name), &format!("a {} light needs a {}", self.shape, name));
+        let radius = || match self.radius {
+            Some(radius) if radius <= 0.0 => Err(SceneError::at(file, &field("radius"), "radius must be positive")),
+            Some(radius) => Ok(radius),
+            None => Err(missing("radius")),
+        };
+        let centre = self.pos.to_vector();
+        let shape = match self.shape.as_str() {
+            "rectangle" => {
+                let u = self.u.ok_or_else(|| missing("u"))?.to_vector();
+                let v = self.v.ok_or_else(|| missing("v"))?.to_vector();
+                if u.norm() == 0.0 || v.norm() == 0.0 {
+                    return Err(SceneError::at(file, path, "rectangle edges can't be zero length"));
+                }
+                if u.normalize().dot(&v.normalize()).abs() > 1e-6 {
+                    return Err(SceneError::at(file, path, "rectangle edges u and v must be at right angles"));
+                }
+                AreaShape::Rectangle { centre, u, v }
+            }
+            "disk" => {
+                let normal = self.normal.ok_or_else(|| missing("normal"))?.to_vector();
+                if normal.norm() == 0.0 {
+                    return Err(SceneError::at(file, &field("normal"), "normal can't be zero length"));
+                }
+                AreaShape::Disk { centre, normal: normal.normalize(), radius: radius()? }
+            }
+            "sphere" => AreaShape::Sphere { centre, radius: radius()? },
+            other => {
+                let reason = format!("unknown area light shape '{}', expected one of rectangle, disk, sphere", other);
+                return Err(SceneError::at(file, &field("shape"), &reason));
+            }
+        };
+        if self.samples == 0 {
+            return Err(SceneError::at(file, &field("samples"), "an area light needs at least one sample"));
+        }
+        Ok(AreaLight {
+            shape,
+            colour: self.colour.to_colour(),
+            intensity: self.intensity,
+            samples: self.samples,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MaterialEntry {
+    pub colour: Rgb,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub specular_exp: f64,
+    pub reflectivity: f64,
+    pub transparency: f64,
+    pub ior: f64,
+}
+
+impl Default for MaterialEntry {
+    fn default() -> MaterialEntry {
+        MaterialEntry {
+            colour: white(),
+            diffuse: 0.8,
+            specular: 0.2,
+            specular_exp: 2.0,
+            reflectivity: 0.0,
+            transparency: 0.0,
+            ior: DEFAULT_IOR,
+        }
+    }
+}
+
+impl MaterialEntry {
+    fn to_material(&self) -> Material {
+        Material {
+            colour: self.colour.to_colour(),
+            diffuse: self.diffuse,
+            specular: self.specular,
+            specular_exp: self.specular_exp,
+            reflectivity: self.reflectivity,
+            transparency: self.transparency,
+            ior: self.ior,
+        }
+    }
+
+    fn validate(&self, file: &str, path: &str) -> Result<(), SceneError> {
+        if !(0.0..=1.0).contains(&self.reflectivity) {
+            return Err(SceneError::at(file, &format!("{}.reflectivity", path), "reflectivity must be between 0 and 1"));
+        }
+        if !(0.0..=1.0).contains(&self.transparency) {
+            return Err(SceneError::at(file, &format!("{}.transparency", path), "transparency must be between 0 and 1"));
+        }
+        if self.reflectivity + self.transparency > 1.0 {
+            return Err(SceneError::at(file, path, "reflectivity and transparency can't add up to more than 1"));
+        }
+        if self.ior <= 0.0 {
+            return Err(SceneError::at(file, &format!("{}.ior", path), "ior must be positive"));
+        }
+        Ok(())
+    }
+}
+
+impl MeshEntry {
+    fn into_meshes(self, file: &str, path: &str, dir: &Path) -> Result<Vec<TriangleMesh>, SceneError> {
+        if let Some(material) = &self.material {
+            material.validate(file, &format!("{}.material", path))?;
+        }
+        let transform = match &self.transform {
+            Some(transform) => Some(transform.to_matrix(file, &format!("{}.transform", path))?),
+            None => None
+        };
+        let mut meshes = match (&self.file, &self.vertices) {
+            (Some(_), Some(_)) => {
+                return Err(SceneError::at(file, path, "a mesh takes either a file or inline vertices, not both"));
+            }
+            (Some(obj_file), None) => {
+                let default_material = self.material.as_ref().map(MaterialEntry::to_material)
+                    .unwrap_or_else(|| MaterialEntry::default().to_material());
+                let mut meshes = obj::load_obj(&dir.join(obj_file), &default_material)
+                    .map_err(|reason| SceneError::at(file, &format!("{}.file", path), &reason))?;
+                if self.material.is_some() {
+                    for mesh in &mut meshes {
+                        mesh.material = default_material.clone();
+                    }
+                }
+                meshes
+            }
+            (None, Some(_)) => vec![self.into_inline_mesh(file, path)?],
+            (None, None) => {
+                return Err(SceneError::at(file, path, "a mesh needs either a file or vertices and indices"));
+            }
+        };
+        if let Some(transform) = transform {
+            for mesh in &mut meshes {
+                mesh.transform(&transform);
+            }
+        }
+        Ok(meshes)
+    }
+
+    fn into_inline_mesh(self, file: &str, path: &str) -> Result<TriangleMesh, SceneError> {
+        let vertices = self.vertices.unwrap_or_default();
+        let indices = self.indices.unwrap_or_default();
+        let vertex_count = vertices.len();
+        if indices.is_empty() {
+            return Err(SceneError::at(file, &format!("{}.indices", path), "a mesh needs at least one triangle"));
+        }
+        for (i, triangle) in indices.iter().enumerate() {
+            if triangle.iter().any(|&index| index >= vertex_count) {
+                return Err(SceneError::at(file, &format!("{}.indices[{}]", path, i),
+                                          &format!("index out of range, the mesh has {} vertices", vertex_count)));
+            }
+        }
+        if let Some(normals) = &self.normals {
+            if normals.len() != vertex_count {
+                return Err(SceneError::at(file, &format!("{}.normals", path),
+                                          &format!("expected one normal per vertex ({}), got {}", vertex_count, normals.len())));
+            }
+            if let Some(j) = normals.iter().position(|normal| normal.iter().all(|&axis| axis == 0.0)) {
+                return Err(SceneError::at(file, &format!("{}.normals[{}]", path, j), "normal can't be zero length"));
+            }
+        }
+        if let Some(uvs) = &self.uvs {
+            if uvs.len() != vertex_count {
+                return Err(SceneError::at(file, &format!("{}.uvs", path),
+                                          &format!("expected one uv per vertex ({}), got {}", vertex_count, uvs.len())));
+            }
+        }
+
+        Ok(TriangleMesh::new(
+            vertices.iter().map(|&[x, y, z]| Vector3::new(x, y, z)).collect(),
+            self.normals.map(|normals| normals.iter().map(|&[x, y, z]| Vector3::new(x, y, z)).collect()),
+            self.uvs.map(|uvs| uvs.iter().map(|&[u, v]| Vector2::new(u, v)).collect()),
+            indices,
+            self.material.unwrap_or_default().to_material(),
+        ))
+    }
+}
+
+fn default_ambient() -> f64 { 0.1 }
+fn default_fov() -> f64 { 90.0 }
+fn default_spot_inner() -> f64 { 20.0 }
+fn default_spot_outer() -> f64 { 30.0 }
+fn default_area_samples() -> u32 { 16 }
+fn default_ao_samples() -> u32 { DEFAULT_AO_SAMPLES }
+fn default_ao_distance() -> f64 { DEFAULT_AO_DISTANCE }
+fn default_max_depth() -> u32 { DEFAULT_MAX_DEPTH }
+fn default_white_point() -> f64 { DisplayTransform::default().white_point }
+fn one() -> f64 { 1.0 }
+fn origin() -> Xyz { Xyz { x: 0.0, y: 0.0, z: 0.0 } }
+fn forward() -> Xyz { Xyz { x: 0.0, y: 0.0, z: 1.0 } }
+fn world_up() -> Xyz { Xyz { x: 0.0, y: 1.0, z: 0.0 } }
+fn white() -> Rgb { Rgb { r: 255, g: 255, b: 255 } }
+fn unit_scale() -> ScaleEntry { ScaleEntry::Uniform(1.0) }
+
+/// Reads and validates a scene file, turning it into something ready to render.
+pub fn load(file: &str) -> Result<SceneState, SceneError> {
+    load_with(file, &IntegratorRegistry::default())
+}
+
+/// Like `load`, picking the scene's integrator from `integrators`.
+pub fn load_with(file: &str, integrators: &IntegratorRegistry) -> Result<SceneState, SceneError> {
+    let yaml = std::fs::read_to_string(file).map_err(|err| SceneError {
+        file: file.to_string(),
+        location: None,
+        path: None,
+        reason: err.to_string(),
+    })?;
+    parse_with(file, &yaml, integrators)
+}
+
+/// Parses scene YAML, `file` is only used to label errors.
+pub fn parse(file: &str, yaml: &str) -> Result<SceneState, SceneError> {
+    parse_with(file, yaml, &IntegratorRegistry::default())
+}
+
+/// Like `parse`, picking the scene's integrator from `integrators`.
+pub fn parse_with(file: &str, yaml: &str, integrators: &IntegratorRegistry) -> Result<SceneState, SceneError> {
+    let mut track = Track::new();
+    let scene_file: SceneFile = serde_yaml::seed::from_str_seed(yaml, Tracked(&mut track)).map_err(|err| {
+        let path = track.path().to_string();
+        let location = err.location().map(|location| (location.line(), location.column()));
+        SceneError {
+            file: file.to_string(),
+            location,
+            path: if path == "." { None } else { Some(path) },
+            reason: yaml_reason(&err, location),
+        }
+    })?;
+    scene_file.into_scene(file, integrators)
+}
+
+/// serde_yaml's message without the key path and location it wraps round it, which `SceneError`
+/// shows in its own format.
+fn yaml_reason(err: &serde_yaml::Error, location: Option<(usize, usize)>) -> String {
+    let message = err.to_string();
+    let message = match location {
+        Some((line, column)) => message.trim_end_matches(&format!(" at line {} column {}", line, column)).to_string(),
+        None => message
+    };
+    //a key path has no spaces, where serde's own messages start with a few words
+    match message.split_once(": ") {
+        Some((path, reason)) if !path.contains(char::is_whitespace) => reason.to_string(),
+        _ => message
+    }
+}
+
+/// Deserialises a `SceneFile` straight from serde_yaml's deserializer, so errors keep where in
+/// the text they happened, noting the key they happened at in `track`.
+struct Tracked<'a>(&'a mut Track);
+
+impl<'de, 'a> DeserializeSeed<'de> for Tracked<'a> {
+    type Value = SceneFile;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<SceneFile, D::Error> {
+        SceneFile::deserialize(serde_path_to_error::Deserializer::new(deserializer, self.0))
+    }
+}
+
+impl SceneFile {
+    pub fn into_scene(self, file: &str, integrators: &IntegratorRegistry) -> Result<SceneState, SceneError> {
+        let camera_pos = self.camera.pos.to_vector();
+        let camera_up = self.camera.up.to_vector();
+        let mut camera = match (self.camera.dir, self.camera.look_at) {
+            (Some(_), Some(_)) => return Err(SceneError::at(file, "camera", "give either dir or look_at, not both")),
+            (_, Some(target)) => {
+                if target.to_vector() == camera_pos {
+                    return Err(SceneError::at(file, "camera.look_at", "can't look at the camera's own position"));
+                }
+                Camera::look_at(camera_pos, target.to_vector(), camera_up, default_fov())
+            }
+            (dir, None) => {
+                let dir = dir.unwrap_or_else(forward).to_vector();
+                if dir.norm() == 0.0 {
+                    return Err(SceneError::at(file, "camera.dir", "direction can't be zero length"));
+                }
+                Camera { up: camera_up, ..Camera::new(camera_pos, dir, default_fov()) }
+            }
+        };
+        camera.roll = self.camera.roll;
+        if let Some(reason) = camera.degenerate() {
+            return Err(SceneError::at(file, "camera.up", reason));
+        }
+        let (projection, fov) = self.camera.to_projection(file, camera.dir.magnitude())?;
+        camera.projection = projection;
+        camera.fov = fov;
+        camera.lens = self.camera.to_lens(file, camera.dir.magnitude())?;
+        if camera.lens.is_some() && !camera.projection.supports_lens() {
+            return Err(SceneError::at(file, "camera", "depth of field needs a perspective or orthographic camera"));
+        }
+
+        let tone_map = match &self.tone_map {
+            Some(name) => name.parse::<ToneMap>().map_err(|reason| SceneError::at(file, "tone_map", &reason))?,
+            None => ToneMap::Clamp
+        };
+        let integrator = integrators.create(self.integrator.as_deref().unwrap_or("phong"))
+            .map_err(|reason| SceneError::at(file, "integrator", &reason))?;
+        let sampler = match &self.sampler {
+            Some(name) => name.parse::<SamplerType>().map_err(|reason| SceneError::at(file, "sampler", &reason))?,
+            None => SamplerType::default()
+        };
+        let ambient_occlusion = match &self.ambient_occlusion {
+            Some(entry) => Some(entry.to_occlusion(file)?),
+            None => None
+        };
+        if self.white_point <= 0.0 {
+            return Err(SceneError::at(file, "white_point", "white point must be positive"));
+        }
+
+        let mut geom: Vec<Box<dyn Drawable>> = vec![];
+        for (i, sphere) in self.spheres.into_iter().enumerate() {
+            if sphere.radius <= 0.0 {
+                return Err(SceneError::at(file, &format!("spheres[{}].radius", i), "radius must be positive"));
+            }
+            sphere.material.validate(file, &format!("spheres[{}].material", i))?;
+            geom.push(Box::new(Sphere {
+                pos: sphere.pos.to_vector(),
+                radius: sphere.radius,
+                material: sphere.material.to_material(),
+            }));
+        }
+
+        for (i, plane) in self.planes.into_iter().enumerate() {
+            if plane.norm.to_vector().norm() == 0.0 {
+                return Err(SceneError::at(file, &format!("planes[{}].norm", i), "normal can't be zero length"));
+            }
+            plane.material.validate(file, &format!("planes[{}].material", i))?;
+            geom.push(Box::new(Plane {
+                point: plane.pos.to_vector(),
+                norm: plane.norm.to_vector().normalize(),
+                material: plane.material.to_material(),
+            }));
+        }
+
+        for (i, triangle) in self.triangles.into_iter().enumerate() {
+            triangle.material.validate(file, &format!("triangles[{}].material", i))?;
+            geom.push(Box::new(Triangle {
+                a: triangle.a.to_vector(),
+                b: triangle.b.to_vector(),
+                c: triangle.c.to_vector(),
+                material: triangle.material.to_material(),
+            }));
+        }
+
+        let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+        for (i, mesh) in self.meshes.into_iter().enumerate() {
+            for mesh in mesh.into_meshes(file, &format!("meshes[{}]", i), dir)? {
+                geom.push(Box::new(mesh));
+            }
+        }
+
+        let mut lights: Vec<Box<dyn Light>> = vec![];
+        for light in self.point_lights {
+            lights.push(Box::new(PointLight {
+                pos: light.pos.to_vector(),
+                colour: light.colour.to_colour(),
+                intensity: light.intensity,
+            }));
+        }
+        for (i, light) in self.directional_lights.into_iter().enumerate() {
+            let dir = light.dir.to_vector();
+            if dir.norm() == 0.0 {
+                return Err(SceneError::at(file, &format!("directional_lights[{}].dir", i), "direction can't be zero length"));
+            }
+            lights.push(Box::new(DirectionalLight {
+                dir,
+                colour: light.colour.to_colour(),
+                intensity: light.intensity,
+            }));
+        }
+        for (i, light) in self.spot_lights.into_iter().enumerate() {
+            let path = format!("spot_lights[{}]", i);
+            let dir = light.dir.to_vector();
+            if dir.norm() == 0.0 {
+                return Err(SceneError::at(file, &format!("{}.dir", path), "direction can't be zero length"));
+            }
+            if light.outer_angle <= 0.0 || light.outer_angle > 180.0 {
+                return Err(SceneError::at(file, &format!("{}.outer_angle", path), "outer angle must be between 0 and 180 degrees"));
+            }
+            if light.inner_angle < 0.0 || light.inner_angle > light.outer_angle {
+                return Err(SceneError::at(file, &format!("{}.inner_angle", path), "inner angle must be between 0 and the outer angle"));
+            }
+            lights.push(Box::new(SpotLight {
+                pos: light.pos.to_vector(),
+                dir: dir.normalize(),
+                inner: light.inner_angle,
+                outer: light.outer_angle,
+                colour: light.colour.to_colour(),
+                intensity: light.intensity,
+            }));
+        }
+        for (i, light) in self.area_lights.iter().enumerate() {
+            lights.push(Box::new(light.to_light(file, &format!("area_lights[{}]", i))?));
+        }
+
+        let mut scene = SceneState::new(geom, lights, camera, self.ambient, self.background.to_colour());
+        scene.ambient_occlusion = ambient_occlusion;
+        scene.sampler = sampler.sampler();
+        scene.seed = self.seed;
+        scene.max_depth = self.max_depth;
+        scene.integrator = integrator;
+        scene.display = DisplayTransform {
+            tone_map,
+            exposure: self.exposure,
+            white_point: self.white_point,
+        };
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::{parse, parse_with};
+    use crate::tracer::integrator::IntegratorRegistry;
+    use crate::tracer::occlusion::AmbientOcclusion;
+    use crate::tracer::phong::Phong;
+    use crate::tracer::lens::{Aperture, ThinLens};
+    use crate::tracer::projection::View;
+    use crate::tracer::colour::tonemap::{DisplayTransform, ToneMap};
+
+    #[test]
+    fn test_minimal_scene_uses_defaults() {
+        let scene = parse("test.yml", "camera: {}\nspheres:\n  - pos: {z: 5.0}\n    radius: 1.0\n").unwrap();
+        assert_eq!(scene.geom().len(), 1);
+        assert!(scene.lights.is_empty());
+        assert_eq!(scene.camera.fov, 90.0);
+        assert_eq!(scene.camera.dir[2], 1.0);
+        assert_eq!(scene.ambient, 0.1);
+        assert_eq!(scene.display, DisplayTransform::default());
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let err = parse("test.yml", "camera: {}\nspheres:\n  - pos: {z: 5.0}\n    radius: 1.0\n    material:\n      difuse: 0.5\n")
+            .err().unwrap();
+        assert_eq!(err.file, "test.yml");
+        assert_eq!(err.location, Some((6, 7)));
+        assert_eq!(err.path.as_deref(), Some("spheres[0].material.difuse"));
+        assert!(err.reason.starts_with("unknown field `difuse`"), "{}", err.reason);
+        assert!(err.to_string().starts_with("test.yml:6:7: spheres[0].material.difuse: unknown field"));
+
+        let err = parse("test.yml", "camera: {}\nplanes:\n  - norm: {y: 1.0}\n    material:\n      colour: {r: 300}\n")
+            .err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("planes[0].material.colour.r"));
+        assert_eq!(err.to_string(), "test.yml:5:19: planes[0].material.colour.r: invalid value: integer `300`, expected u8");
+
+        //YAML that doesn't parse at all still says where
+        let err = parse("test.yml", "camera: {}\nspheres: [\n").err().unwrap();
+        assert!(err.location.is_some() && err.path.is_none(), "{}", err);
+
+        let err = parse("test.yml", "camera: {}\nspheres:\n  - pos: {z: 5.0}\n    radius: -1.0\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.yml: spheres[0].radius: radius must be positive");
+
+        let err = parse("test.yml", "camera: {}\nspheres:\n  - pos: {z: 5.0}\n    radius: 1.0\n    material: {reflectivity: 0.6, transparency: 0.6}\n")
+            .err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("spheres[0].material"));
+    }
+
+    #[test]
+    fn test_camera_orientation() {
+        let scene = parse("test.yml", "camera: {pos: {y: 5}, look_at: {}, up: {z: 1}, roll: 30}
+").unwrap();
+        assert_eq!(scene.camera.dir, nalgebra::Vector3::new(0.0, -5.0, 0.0));
+        assert_eq!(scene.camera.roll, 30.0);
+
+        let err = parse("test.yml", "camera: {pos: {y: 5}, look_at: {}}
+").err().unwrap();
+        assert_eq!(err.to_string(), "test.yml: camera.up: up can't be parallel to the view direction");
+        let err = parse("test.yml", "camera: {dir: {z: 1}, look_at: {z: 1}}
+").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera"));
+        let err = parse("test.yml", "camera: {look_at: {}}
+").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.look_at"));
+        let err = parse("test.yml", "camera: {up: {}}
+").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.up"));
+    }
+
+    #[test]
+    fn test_camera_lens() {
+        let scene = parse("test.yml", "camera: {look_at: {z: 4}, aperture: 0.1, blades: 6}\n").unwrap();
+        assert_eq!(scene.camera.lens, Some(ThinLens { radius: 0.1, focus_dist: 4.0, aperture: Aperture::Polygon(6) }));
+        let scene = parse("test.yml", "camera: {fov: 90, f_stop: 2, focus_dist: 3}\n").unwrap();
+        assert_eq!(scene.camera.lens.unwrap().radius, ThinLens::radius_for_f_stop(2.0, 90.0));
+        assert!(parse("test.yml", "camera: {}\n").unwrap().camera.lens.is_none());
+
+        let err = parse("test.yml", "camera: {aperture: 0.1, f_stop: 2}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera"));
+        let err = parse("test.yml", "camera: {aperture: 0.1, blades: 2}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.blades"));
+        let err = parse("test.yml", "camera: {aperture: 0.1, focus_dist: 0}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.focus_dist"));
+    }
+
+    #[test]
+    fn test_camera_projection() {
+        let scene = parse("test.yml", "camera: {type: fisheye}\n").unwrap();
+        assert_eq!(scene.camera.fov, 180.0);
+        let view = View::new(&scene.camera, 10.0, 10.0);
+        assert!(scene.camera.projection.ray(&view, 0.0, 0.0, (0.0, 0.0)).is_none());
+        //a default orthographic view frames the same width as perspective does at the end of dir
+        let scene = parse("test.yml", "camera: {type: orthographic, dir: {z: 2}}\n").unwrap();
+        let view = View::new(&scene.camera, 10.0, 10.0);
+        let (orig, dir) = scene.camera.projection.ray(&view, 0.0, 5.0, (0.0, 0.0)).unwrap();
+        approx::assert_ulps_eq!(orig[0], -2.0, max_ulps = 4);
+        assert_eq!(dir, nalgebra::Vector3::new(0.0, 0.0, 1.0));
+        parse("test.yml", "camera: {type: equirectangular}\n").unwrap();
+
+        let err = parse("test.yml", "camera: {type: pinhole}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.type"));
+        let err = parse("test.yml", "camera: {width: 3}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.width"));
+        let err = parse("test.yml", "camera: {type: equirectangular, fov: 90}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("camera.fov"));
+        let err = parse("test.yml", "camera: {type: fisheye, aperture: 0.1}\n").err().unwrap();
+        assert!(err.reason.contains("depth of field"));
+    }
+
+    #[test]
+    fn test_directional_lights() {
+        let scene = parse("test.yml", "camera: {}\npoint_lights:\n  - pos: {y: 5}\ndirectional_lights:\n  - dir: {y: -1}\n    intensity: 0.5\n").unwrap();
+        assert_eq!(scene.lights.len(), 2);
+        let err = parse("test.yml", "camera: {}\ndirectional_lights:\n  - dir: {}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("directional_lights[0].dir"));
+    }
+
+    #[test]
+    fn test_spot_lights() {
+        let scene = parse("test.yml", "camera: {}\nspot_lights:\n  - {pos: {y: 5}, dir: {y: -1}, outer_angle: 45}\n").unwrap();
+        assert_eq!(scene.lights.len(), 1);
+        let err = parse("test.yml", "camera: {}\nspot_lights:\n  - {pos: {y: 5}, dir: {y: -1}, inner_angle: 50, outer_angle: 45}\n")
+            .err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("spot_lights[0].inner_angle"));
+    }
+
+    #[test]
+    fn test_area_lights() {
+        let yaml = "camera: {}\narea_lights:\n  - {shape: rectangle, pos: {y: 5}, u: {x: 2}, v: {z: -1}, samples: 4}\n  \
+                    - {shape: disk, pos: {y: 5}, normal: {y: -1}, radius: 0.5}\n  - {shape: sphere, pos: {y: 5}, radius: 0.5}\n";
+        let scene = parse("test.yml", yaml).unwrap();
+        assert_eq!(scene.lights.len(), 3);
+        assert_eq!(scene.lights[0].samples(), 4);
+        assert_eq!(scene.lights[1].samples(), 16);
+
+        let err = parse("test.yml", "camera: {}\narea_lights:\n  - {shape: disk, pos: {y: 5}, radius: 0.5}\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.yml: area_lights[0].normal: a disk light needs a normal");
+        let err = parse("test.yml", "camera: {}\narea_lights:\n  - {shape: rectangle, pos: {y: 5}, u: {x: 1}, v: {x: 1, z: 1}}\n")
+            .err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("area_lights[0]"));
+        let err = parse("test.yml", "camera: {}\narea_lights:\n  - {shape: tube, pos: {y: 5}}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("area_lights[0].shape"));
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        assert_eq!(parse("test.yml", "camera: {}\n").unwrap().ambient_occlusion, None);
+        let scene = parse("test.yml", "camera: {}\nambient_occlusion: {distance: 2.5}\n").unwrap();
+        assert_eq!(scene.ambient_occlusion, Some(AmbientOcclusion { samples: 16, max_dist: 2.5 }));
+        let scene = parse("test.yml", "camera: {}\nambient_occlusion: {samples: 4}\nintegrator: ao\n").unwrap();
+        assert_eq!(scene.ambient_occlusion.unwrap().samples, 4);
+        assert_eq!(scene.integrator.name(), "ao");
+
+        let err = parse("test.yml", "camera: {}\nambient_occlusion: {samples: 0}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("ambient_occlusion.samples"));
+        let err = parse("test.yml", "camera: {}\nambient_occlusion: {distance: -1}\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("ambient_occlusion.distance"));
+    }
+
+    #[test]
+    fn test_sampler() {
+        let scene = parse("test.yml", "camera: {}\n").unwrap();
+        assert_eq!(format!("{:?}", scene.sampler), "Stratified");
+        assert_eq!(scene.seed, 0);
+        let scene = parse("test.yml", "camera: {}\nsampler: sobol\nseed: 42\n").unwrap();
+        assert_eq!(format!("{:?}", scene.sampler), "Sobol");
+        assert_eq!(scene.seed, 42);
+        let err = parse("test.yml", "camera: {}\nsampler: random\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("sampler"));
+    }
+
+    #[test]
+    fn test_display_settings() {
+        let scene = parse("test.yml", "camera: {}\ntone_map: aces\nexposure: -1.5\n").unwrap();
+        assert_eq!(scene.display.tone_map, ToneMap::Aces);
+        assert_eq!(scene.display.exposure, -1.5);
+
+        let err = parse("test.yml", "camera: {}\ntone_map: filmic\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("tone_map"));
+    }
+
+    #[test]
+    fn test_integrator() {
+        assert_eq!(parse("test.yml", "camera: {}\n").unwrap().integrator.name(), "phong");
+        let scene = parse("test.yml", "camera: {}\nintegrator: path\nmax_depth: 12\n").unwrap();
+        assert_eq!(scene.integrator.name(), "path");
+        assert_eq!(scene.max_depth, 12);
+        let err = parse("test.yml", "camera: {}\nintegrator: bidirectional\n").err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("integrator"));
+
+        //integrators registered outside the tracer can be picked by name too
+        let mut integrators = IntegratorRegistry::default();
+        integrators.register("bidirectional", || Box::new(Phong));
+        assert!(parse_with("test.yml", "camera: {}\nintegrator: bidirectional\n", &integrators).is_ok());
+    }
+
+    #[test]
+    fn test_inline_mesh() {
+        let yaml = "camera: {}\nmeshes:\n  - vertices: [[0, 0, 5], [1, 0, 5], [0, 1, 5]]\n    indices: [[0, 1, 2]]\n";
+        let scene = parse("test.yml", yaml).unwrap();
+        assert_eq!(scene.geom().len(), 1);
+
+        let yaml = "camera: {}\nmeshes:\n  - vertices: [[0, 0, 5], [1, 0, 5], [0, 1, 5]]\n    indices: [[0, 1, 2]]\n    \
+                    normals: [[0, 0, -1], [0, 0, 0], [0, 0, -1]]\n";
+        let err = parse("test.yml", yaml).err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("meshes[0].normals[1]"));
+
+        let yaml = "camera: {}\nmeshes:\n  - vertices: [[0, 0, 5], [1, 0, 5], [0, 1, 5]]\n    indices: [[0, 1, 3]]\n";
+        let err = parse("test.yml", yaml).err().unwrap();
+        assert_eq!(err.path.as_deref(), Some("meshes[0].indices[0]"));
+    }
+}
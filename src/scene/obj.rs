@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::tracer::colour::{Colour, Material};
+use crate::tracer::geom::mesh::TriangleMesh;
+
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+/// Collects the faces of one group/material pair, re-indexing the OBJ's separate position, uv
+/// and normal lists into a single set of mesh vertices.
+struct MeshBuilder {
+    material: Option<String>,
+    vertex_map: HashMap<VertexKey, usize>,
+    positions: Vec<Vector3<f64>>,
+    normals: Vec<Vector3<f64>>,
+    uvs: Vec<Vector2<f64>>,
+    all_normals: bool,
+    all_uvs: bool,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(material: Option<String>) -> MeshBuilder {
+        MeshBuilder {
+            material,
+            vertex_map: HashMap::new(),
+            positions: vec![],
+            normals: vec![],
+            uvs: vec![],
+            all_normals: true,
+            all_uvs: true,
+            indices: vec![],
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey, obj: &ObjData) -> usize {
+        if let Some(&index) = self.vertex_map.get(&key) {
+            return index;
+        }
+        let (position, uv, normal) = key;
+        self.positions.push(obj.positions[position]);
+        match uv {
+            Some(uv) => self.uvs.push(obj.uvs[uv]),
+            None => {
+                self.all_uvs = false;
+                self.uvs.push(Vector2::zeros());
+            }
+        }
+        match normal {
+            Some(normal) => self.normals.push(obj.normals[normal]),
+            None => {
+                self.all_normals = false;
+                self.normals.push(Vector3::zeros());
+            }
+        }
+        let index = self.positions.len() - 1;
+        self.vertex_map.insert(key, index);
+        index
+    }
+
+    fn build(self, materials: &HashMap<String, Material>, default_material: &Material) -> TriangleMesh {
+        let material = self.material
+            .and_then(|name| materials.get(&name).cloned())
+            .unwrap_or_else(|| default_material.clone());
+        TriangleMesh::new(
+            self.positions,
+            if self.all_normals { Some(self.normals) } else { None },
+            if self.all_uvs { Some(self.uvs) } else { None },
+            self.indices,
+            material,
+        )
+    }
+}
+
+struct ObjData {
+    positions: Vec<Vector3<f64>>,
+    normals: Vec<Vector3<f64>>,
+    uvs: Vec<Vector2<f64>>,
+}
+
+/// Loads a Wavefront OBJ along with any MTL libraries it references, giving one mesh per group
+/// and material. Faces without a material from the MTL get `default_material`, a `usemtl`
+/// naming a material no library defined is warned about on stderr, as exported models often
+/// come with a partial or missing MTL.
+pub fn load_obj(path: &Path, default_material: &Material) -> Result<Vec<TriangleMesh>, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj(&source, &path.display().to_string(), dir, default_material)
+}
+
+pub fn parse_obj(source: &str, name: &str, dir: &Path, default_material: &Material) -> Result<Vec<TriangleMesh>, String> {
+    let mut obj = ObjData { positions: vec![], normals: vec![], uvs: vec![] };
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut meshes: Vec<TriangleMesh> = vec![];
+    let mut current = MeshBuilder::new(None);
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |reason: &str| format!("{}:{}: {}", name, line_number + 1, reason);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => obj.positions.push(parse_vector(&args).ok_or_else(|| error("expected x y z for v"))?),
+            "vn" => {
+                let normal = parse_vector(&args).ok_or_else(|| error("expected x y z for vn"))?;
+                if normal.norm() == 0.0 {
+                    return Err(error("vn is zero length, so has no direction"));
+                }
+                obj.normals.push(normal.normalize());
+            }
+            "vt" => {
+                let u = args.first().and_then(|u| u.parse().ok()).ok_or_else(|| error("expected u [v] for vt"))?;
+                let v = match args.get(1) {
+                    Some(v) => v.parse().map_err(|_| error("expected u [v] for vt"))?,
+                    None => 0.0
+                };
+                obj.uvs.push(Vector2::new(u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error("a face needs at least three vertices"));
+                }
+                let mut face = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let key = parse_face_vertex(arg, &obj).map_err(|reason| error(&reason))?;
+                    face.push(current.vertex(key, &obj));
+                }
+                //Fan out polygons into triangles
+                for i in 1..face.len() - 1 {
+                    current.indices.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            "g" | "o" | "usemtl" => {
+                let material = if keyword == "usemtl" {
+                    let material = args.join(" ");
+                    if !materials.contains_key(&material) {
+                        eprintln!("warning: {}", error(&format!("material '{}' is not in any loaded mtllib, using the default",
+                                                                 material)));
+                    }
+                    Some(material)
+                } else {
+                    current.material.clone()
+                };
+                let finished = std::mem::replace(&mut current, MeshBuilder::new(material));
+                if !finished.indices.is_empty() {
+                    meshes.push(finished.build(&materials, default_material));
+                }
+            }
+            "mtllib" => {
+                for library in &args {
+                    let mtl_path = dir.join(library);
+                    let mtl_source = std::fs::read_to_string(&mtl_path)
+                        .map_err(|err| error(&format!("{}: {}", mtl_path.display(), err)))?;
+                    materials.extend(parse_mtl(&mtl_source, &mtl_path.display().to_string(), default_material)?);
+                }
+            }
+            //smoothing groups, lines and points don't mean anything to the tracer
+            _ => {}
+        }
+    }
+    if !current.indices.is_empty() {
+        meshes.push(current.build(&materials, default_material));
+    }
+    if meshes.is_empty() {
+        return Err(format!("{}: no faces found", name));
+    }
+    Ok(meshes)
+}
+
+/// Parses an MTL library onto the tracer's material model. `Kd` becomes the colour scaled by
+/// the diffuse weight, the strongest `Ks` channel the specular weight and `Ns` its exponent.
+/// Anything not given comes from `default_material`.
+pub fn parse_mtl(source: &str, name: &str, default_material: &Material) -> Result<HashMap<String, Material>, String> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |reason: &str| format!("{}:{}: {}", name, line_number + 1, reason);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((args.join(" "), default_material.clone()));
+            continue;
+        }
+        let material = match &mut current {
+            Some((_, material)) => material,
+            None => continue
+        };
+        match keyword {
+            "Kd" => {
+                let kd = parse_vector(&args).ok_or_else(|| error("expected r g b for Kd"))?;
+                let diffuse = kd.max();
+                material.diffuse = diffuse;
+                material.colour = if diffuse > 0.0 {
+                    Colour::new(kd[0] / diffuse, kd[1] / diffuse, kd[2] / diffuse)
+                } else {
+                    Colour::black()
+                };
+            }
+            "Ks" => material.specular = parse_vector(&args).ok_or_else(|| error("expected r g b for Ks"))?.max(),
+            "Ns" => material.specular_exp = args.first().and_then(|ns| ns.parse().ok())
+                .ok_or_else(|| error("expected a number for Ns"))?,
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+fn parse_vector(args: &[&str]) -> Option<Vector3<f64>> {
+    if args.len() < 3 {
+        return None;
+    }
+    Some(Vector3::new(args[0].parse().ok()?, args[1].parse().ok()?, args[2].parse().ok()?))
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, OBJ indices count from 1 and negative ones count
+/// back from the last element read so far.
+fn parse_face_vertex(arg: &str, obj: &ObjData) -> Result<VertexKey, String> {
+    let mut parts = arg.split('/');
+    let position = resolve_index(parts.next(), obj.positions.len(), "vertex")?
+        .ok_or_else(|| format!("face vertex {} has no position", arg))?;
+    let uv = resolve_index(parts.next(), obj.uvs.len(), "texture coordinate")?;
+    let normal = resolve_index(parts.next(), obj.normals.len(), "normal")?;
+    Ok((position, uv, normal))
+}
+
+fn resolve_index(part: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+    let part = match part {
+        Some(part) if !part.is_empty() => part,
+        _ => return Ok(None)
+    };
+    let index: i64 = part.parse().map_err(|_| format!("bad {} index {}", kind, part))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} out of range, only {} defined", kind, index, count));
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::scene::obj::{parse_mtl, parse_obj};
+    use crate::tracer::colour::{Colour, Material};
+
+    fn default_material() -> Material {
+        Material::new(Colour::white(), 0.8, 0.2, 2.0)
+    }
+
+    #[test]
+    fn test_groups_and_polygons() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 -1\nvt 0 0\n\
+                      g first\nf 1//1 2//1 3//1 4//1\n\
+                      g second\nf -4/1 -3/1 -2/1\n";
+        let meshes = parse_obj(source, "test.obj", Path::new(""), &default_material()).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].indices(), vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(meshes[0].normals.is_some());
+        assert!(meshes[0].uvs.is_none());
+        assert_eq!(meshes[1].positions().len(), 3);
+        assert!(meshes[1].normals.is_none());
+        assert!(meshes[1].uvs.is_some());
+    }
+
+    #[test]
+    fn test_bad_index_reports_line() {
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n", "test.obj", Path::new(""), &default_material())
+            .err().unwrap();
+        assert!(err.starts_with("test.obj:3:"), "{}", err);
+
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 0\n", "test.obj", Path::new(""), &default_material())
+            .err().unwrap();
+        assert!(err.starts_with("test.obj:4:") && err.contains("zero length"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_material_falls_back() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n";
+        let meshes = parse_obj(source, "test.obj", Path::new(""), &default_material()).unwrap();
+        assert!(meshes[0].material == default_material());
+    }
+
+    #[test]
+    fn test_mtl_mapping() {
+        let materials = parse_mtl("newmtl red\nKd 0.5 0.25 0.0\nKs 0.1 0.3 0.2\nNs 20\nnewmtl plain\n",
+                                  "test.mtl", &default_material()).unwrap();
+        let red = &materials["red"];
+        assert_eq!(red.diffuse, 0.5);
+        assert_eq!(red.colour, Colour::new(1.0, 0.5, 0.0));
+        assert_eq!(red.specular, 0.3);
+        assert_eq!(red.specular_exp, 20.0);
+        assert_eq!(materials["plain"].diffuse, 0.8);
+    }
+}
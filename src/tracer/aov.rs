@@ -0,0 +1,167 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+
+use crate::tracer::colour::{Colour, RGB};
+use crate::tracer::colour::tonemap::DisplayTransform;
+
+/// Auxiliary passes a render can fill alongside the beauty image, all taken from the first
+/// surface each camera ray hits.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Aov {
+    /// Distance along the camera ray, infinite where nothing was hit.
+    Depth,
+    /// World space shading normal facing the camera.
+    Normal,
+    Albedo,
+    /// Index of the object in the scene, -1 for the background.
+    ObjectId,
+    /// Objects with identical materials share an id, -1 for the background.
+    MaterialId,
+    /// Light from the scene's lights at the first hit, before any reflection or refraction.
+    Direct,
+    /// The ambient term at the first hit.
+    Ambient,
+}
+
+const AOV_NAMES: [(&str, Aov); 7] = [
+    ("depth", Aov::Depth),
+    ("normal", Aov::Normal),
+    ("albedo", Aov::Albedo),
+    ("object_id", Aov::ObjectId),
+    ("material_id", Aov::MaterialId),
+    ("direct", Aov::Direct),
+    ("ambient", Aov::Ambient),
+];
+
+impl Aov {
+    /// Names for the pass's channels, in the order they're stored in a `Colour`.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Ambient => &["R", "G", "B"],
+        }
+    }
+
+    /// Whether samples are averaged across the pixel, depth and ids come from the first sample
+    /// alone as blending them would give values nothing in the scene has.
+    pub fn is_averaged(&self) -> bool {
+        !matches!(self, Aov::Depth | Aov::ObjectId | Aov::MaterialId)
+    }
+
+    /// Something viewable for 8 bit image formats, which can't hold the raw values.
+    pub fn to_display(&self, value: Colour, display: &DisplayTransform) -> RGB {
+        match self {
+            Aov::Depth => {
+                let near = 1.0 / (1.0 + value.r);
+                Colour::new(near, near, near).to_rgb()
+            }
+            Aov::Normal => (value * 0.5 + Colour::new(0.5, 0.5, 0.5)).to_rgb(),
+            Aov::ObjectId | Aov::MaterialId => id_colour(value.r),
+            Aov::Albedo | Aov::Direct | Aov::Ambient => display.to_display(value),
+        }
+    }
+}
+
+/// Spreads ids across distinct colours, the background stays black.
+fn id_colour(id: f64) -> RGB {
+    if id < 0.0 {
+        return RGB::new(0, 0, 0);
+    }
+    let hash = (id as u32 + 1).wrapping_mul(0x9e37_79b9);
+    RGB::new((hash >> 24) as u8, (hash >> 16) as u8, (hash >> 8) as u8)
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Aov, String> {
+        AOV_NAMES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, aov)| *aov)
+            .ok_or_else(|| format!("unknown AOV '{}', expected one of {}", name,
+                                   AOV_NAMES.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = AOV_NAMES.iter().find(|(_, aov)| aov == self).map(|(name, _)| *name).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses a comma separated list of AOV names.
+pub fn parse_list(names: &str) -> Result<Vec<Aov>, String> {
+    names.split(',').map(|name| name.trim().parse()).collect()
+}
+
+/// What a single camera ray saw, filled in by the tracer as it shades the first hit.
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vector3<f64>,
+    pub albedo: Colour,
+    pub object_id: Option<usize>,
+    pub material_id: Option<usize>,
+    pub direct: Colour,
+    pub ambient: Colour,
+}
+
+impl Default for AovSample {
+    fn default() -> AovSample {
+        AovSample {
+            depth: f64::INFINITY,
+            normal: Vector3::zeros(),
+            albedo: Colour::black(),
+            object_id: None,
+            material_id: None,
+            direct: Colour::black(),
+            ambient: Colour::black(),
+        }
+    }
+}
+
+impl AovSample {
+    /// The sample's value for a pass, single channel passes use `r`.
+    pub fn value(&self, aov: Aov) -> Colour {
+        let single = |value: f64| Colour::new(value, 0.0, 0.0);
+        let id = |id: Option<usize>| single(id.map_or(-1.0, |id| id as f64));
+        match aov {
+            Aov::Depth => single(self.depth),
+            Aov::Normal => Colour::new(self.normal[0], self.normal[1], self.normal[2]),
+            Aov::Albedo => self.albedo,
+            Aov::ObjectId => id(self.object_id),
+            Aov::MaterialId => id(self.material_id),
+            Aov::Direct => self.direct,
+            Aov::Ambient => self.ambient,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tracer::aov::{parse_list, Aov, AovSample, AOV_NAMES};
+    use crate::tracer::colour::Colour;
+
+    #[test]
+    fn test_names() {
+        for (name, aov) in AOV_NAMES.iter() {
+            assert_eq!(name.parse::<Aov>().unwrap(), *aov);
+            assert_eq!(aov.to_string(), *name);
+        }
+        assert_eq!(parse_list("depth, normal").unwrap(), vec![Aov::Depth, Aov::Normal]);
+        assert!(parse_list("depth,zdepth").unwrap_err().contains("zdepth"));
+    }
+
+    #[test]
+    fn test_background_sample() {
+        let sample = AovSample::default();
+        assert_eq!(sample.value(Aov::Depth).r, f64::INFINITY);
+        assert_eq!(sample.value(Aov::ObjectId).r, -1.0);
+        assert_eq!(sample.value(Aov::Albedo), Colour::black());
+    }
+}
@@ -1,4 +1,102 @@
-//TODO I also want float 0.0-1.0 versions as switching between floats and u8s is annoying me.
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign};
+
+use tonemap::srgb_decode;
+
+pub mod tonemap;
+
+/// Index of refraction materials get when none is given, about that of glass.
+pub const DEFAULT_IOR: f64 = 1.5;
+
+/// Linear RGB radiance. Channels aren't clamped so highlights can go past 1.0 until the image is
+/// quantised for output.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Colour {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Colour {
+    pub fn new(r:f64, g:f64, b:f64) -> Colour {
+        Colour { r, g, b }
+    }
+
+    pub fn black() -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    pub fn white() -> Colour {
+        Colour::new(1.0, 1.0, 1.0)
+    }
+
+    pub fn max_channel(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// Decodes an 8 bit sRGB colour, like the ones written in scene files, to linear.
+    pub fn from_srgb(rgb: RGB) -> Colour {
+        let decode = |channel: u8| srgb_decode(channel as f64 / 255.0);
+        Colour::new(decode(rgb.r), decode(rgb.g), decode(rgb.b))
+    }
+
+    /// Clamps to 0-1 and rounds to 8 bits with no encoding, only meant to be done once a pixel is
+    /// finished and in display space.
+    pub fn to_rgb(&self) -> RGB {
+        RGB::new(quantise(self.r), quantise(self.g), quantise(self.b))
+    }
+}
+
+fn quantise(channel: f64) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Add for Colour {
+    type Output = Colour;
+
+    fn add(self, other: Colour) -> Colour {
+        Colour::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for Colour {
+    fn add_assign(&mut self, other: Colour) {
+        *self = *self + other;
+    }
+}
+
+/// Channel by channel, for filtering light through a surface's colour.
+impl Mul for Colour {
+    type Output = Colour;
+
+    fn mul(self, other: Colour) -> Colour {
+        Colour::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+impl Mul<f64> for Colour {
+    type Output = Colour;
+
+    fn mul(self, scale: f64) -> Colour {
+        Colour::new(self.r * scale, self.g * scale, self.b * scale)
+    }
+}
+
+impl MulAssign<f64> for Colour {
+    fn mul_assign(&mut self, scale: f64) {
+        *self = *self * scale;
+    }
+}
+
+impl Div<f64> for Colour {
+    type Output = Colour;
+
+    fn div(self, scale: f64) -> Colour {
+        Colour::new(self.r / scale, self.g / scale, self.b / scale)
+    }
+}
+
+/// 8 bit colour, what ends up in the frame buffer and image files.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct RGB {
     pub r: u8,
     pub g: u8,
@@ -9,25 +107,60 @@ impl RGB {
     pub fn new(r:u8,g:u8,b:u8) -> RGB {
         RGB { r, g, b}
     }
+}
 
-    pub fn multiply(&self, fraction:f64) -> RGB {
-        RGB {
-            r: mult_channel(self.r,fraction),
-            g: mult_channel(self.g,fraction),
-            b: mult_channel(self.b,fraction),
+#[derive(Clone, PartialEq)]
+pub struct Material {
+    pub colour:Colour,
+    pub diffuse:f64,
+    pub specular:f64,
+    pub specular_exp:f64,
+    /// Fraction of the colour that comes from a mirror reflection.
+    pub reflectivity:f64,
+    /// Fraction of the colour that comes from light passing through, split between reflection and
+    /// refraction by the Fresnel term.
+    pub transparency:f64,
+    /// Index of refraction, only used when the material is transparent.
+    pub ior:f64
+}
+
+impl Material {
+    /// An opaque material with no mirror reflection.
+    pub fn new(colour:Colour, diffuse:f64, specular:f64, specular_exp:f64) -> Material {
+        Material {
+            colour,
+            diffuse,
+            specular,
+            specular_exp,
+            reflectivity: 0.0,
+            transparency: 0.0,
+            ior: DEFAULT_IOR
         }
     }
 }
+#[cfg(test)]
+mod tests {
+    use crate::tracer::colour::{Colour, RGB};
+    use crate::tracer::colour::tonemap::DisplayTransform;
 
-fn mult_channel(chn:u8, fraction: f64 ) -> u8 {
-    let normal = (chn as f64) / 255.0;
-    let fractioned = normal * fraction * 255.0;
-    fractioned as u8
-}
+    #[test]
+    fn test_operators() {
+        let mut colour = Colour::new(0.5, 0.25, 1.0) * Colour::new(1.0, 2.0, 0.5) + Colour::new(0.25, 0.25, 0.25);
+        assert_eq!(colour, Colour::new(0.75, 0.75, 0.75));
+        colour *= 2.0;
+        colour += Colour::white();
+        assert_eq!(colour / 2.0, Colour::new(1.25, 1.25, 1.25));
+    }
 
-pub struct Material {
-    pub rgb:RGB,
-    pub diffuse:f64,
-    pub specular:f64,
-    pub specular_exp:f64
-}
\ No newline at end of file
+    #[test]
+    fn test_quantise_once() {
+        //many dim samples that 8 bit maths would have truncated to nothing
+        let mut total = Colour::black();
+        for _ in 0..64 {
+            total += Colour::new(0.003, 0.5, 3.0);
+        }
+        assert_eq!((total / 64.0).to_rgb(), RGB::new(1, 128, 255));
+        let rgb = RGB::new(255, 0, 51);
+        assert_eq!(DisplayTransform::default().to_display(Colour::from_srgb(rgb)), rgb);
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::tracer::colour::{Colour, RGB};
+
+//Uncharted 2 filmic curve constants, from John Hable's GDC talk
+const HABLE_A: f64 = 0.15;
+const HABLE_B: f64 = 0.50;
+const HABLE_C: f64 = 0.10;
+const HABLE_D: f64 = 0.20;
+const HABLE_E: f64 = 0.02;
+const HABLE_F: f64 = 0.30;
+const HABLE_WHITE: f64 = 11.2;
+const HABLE_EXPOSURE_BIAS: f64 = 2.0;
+
+/// How radiance above 1.0 is squeezed into the displayable range. Every operator works on each
+/// channel on its own.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    /// Reinhard that reaches 1.0 at the transform's white point rather than at infinity.
+    ExtendedReinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    Uncharted2,
+}
+
+const TONE_MAP_NAMES: [(&str, ToneMap); 5] = [
+    ("clamp", ToneMap::Clamp),
+    ("reinhard", ToneMap::Reinhard),
+    ("extended-reinhard", ToneMap::ExtendedReinhard),
+    ("aces", ToneMap::Aces),
+    ("uncharted2", ToneMap::Uncharted2),
+];
+
+impl ToneMap {
+    pub fn apply(&self, channel: f64, white_point: f64) -> f64 {
+        let channel = channel.max(0.0);
+        match self {
+            ToneMap::Clamp => channel,
+            ToneMap::Reinhard => channel / (1.0 + channel),
+            ToneMap::ExtendedReinhard => channel * (1.0 + channel / (white_point * white_point)) / (1.0 + channel),
+            ToneMap::Aces => (channel * (2.51 * channel + 0.03)) / (channel * (2.43 * channel + 0.59) + 0.14),
+            ToneMap::Uncharted2 => hable(channel * HABLE_EXPOSURE_BIAS) / hable(HABLE_WHITE),
+        }.clamp(0.0, 1.0)
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    (x * (HABLE_A * x + HABLE_C * HABLE_B) + HABLE_D * HABLE_E) / (x * (HABLE_A * x + HABLE_B) + HABLE_D * HABLE_F)
+        - HABLE_E / HABLE_F
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ToneMap, String> {
+        TONE_MAP_NAMES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, tone_map)| *tone_map)
+            .ok_or_else(|| format!("unknown tone map '{}', expected one of {}", name,
+                                   TONE_MAP_NAMES.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for ToneMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = TONE_MAP_NAMES.iter().find(|(_, tone_map)| tone_map == self).map(|(name, _)| *name).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+/// Turns linear radiance into the 8 bit sRGB a display expects: exposure, then tone mapping,
+/// then the sRGB transfer curve.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplayTransform {
+    pub tone_map: ToneMap,
+    /// In stops, each one doubles the brightness.
+    pub exposure: f64,
+    /// Radiance that maps to full white with the extended Reinhard operator.
+    pub white_point: f64,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> DisplayTransform {
+        DisplayTransform {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl DisplayTransform {
+    pub fn to_display(&self, colour: Colour) -> RGB {
+        let scale = self.exposure.exp2();
+        let map = |channel: f64| srgb_encode(self.tone_map.apply(channel * scale, self.white_point));
+        Colour::new(map(colour.r), map(colour.g), map(colour.b)).to_rgb()
+    }
+}
+
+/// The sRGB transfer function, linear 0-1 to encoded 0-1.
+pub fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_encode`.
+pub fn srgb_decode(encoded: f64) -> f64 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx;
+
+    use crate::tracer::colour::{Colour, RGB};
+    use crate::tracer::colour::tonemap::{DisplayTransform, ToneMap, TONE_MAP_NAMES, srgb_decode, srgb_encode};
+
+    #[test]
+    fn test_srgb_round_trip() {
+        approx::assert_abs_diff_eq!(srgb_encode(0.5), 0.735_357, epsilon = 1e-6);
+        approx::assert_abs_diff_eq!(srgb_decode(0.5), 0.214_041, epsilon = 1e-6);
+        for i in 0..=255 {
+            let encoded = i as f64 / 255.0;
+            approx::assert_abs_diff_eq!(srgb_encode(srgb_decode(encoded)), encoded, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        for (name, tone_map) in TONE_MAP_NAMES.iter() {
+            assert_eq!(name.parse::<ToneMap>().unwrap(), *tone_map);
+            assert_eq!(tone_map.to_string(), *name);
+            assert_eq!(tone_map.apply(0.0, 4.0), 0.0);
+            //never darker for more light, and never past white
+            let mut last = 0.0;
+            for i in 1..200 {
+                let mapped = tone_map.apply(i as f64 * 0.1, 4.0);
+                assert!(mapped >= last && mapped <= 1.0, "{} isn't monotonic", name);
+                last = mapped;
+            }
+        }
+        approx::assert_ulps_eq!(ToneMap::Reinhard.apply(1.0, 4.0), 0.5);
+        approx::assert_ulps_eq!(ToneMap::ExtendedReinhard.apply(4.0, 4.0), 1.0);
+        assert_eq!(ToneMap::Clamp.apply(3.0, 4.0), 1.0);
+        assert!("filmic".parse::<ToneMap>().unwrap_err().contains("aces"));
+    }
+
+    #[test]
+    fn test_exposure() {
+        let display = DisplayTransform { exposure: 1.0, ..DisplayTransform::default() };
+        assert_eq!(display.to_display(Colour::new(0.25, 0.0, 1.0)), DisplayTransform::default().to_display(Colour::new(0.5, 0.0, 1.0)));
+        assert_eq!(DisplayTransform::default().to_display(Colour::new(0.5, 0.0, 1.0)), RGB::new(188, 0, 255));
+    }
+}
@@ -0,0 +1,257 @@
+use nalgebra::Vector3;
+use rand::RngCore;
+
+use crate::tracer::{Ray, SceneState};
+use crate::tracer::aov::AovSample;
+use crate::tracer::colour::Colour;
+use crate::tracer::colour::tonemap::srgb_decode;
+use crate::tracer::integrator::Integrator;
+
+//What a bad sample shows up as, and what the rest of the image is dimmed to around it
+const NAN_FLAG: Colour = Colour { r: 1.0, g: 0.0, b: 1.0 };
+const NAN_BACKDROP: f64 = 0.5;
+
+/// Shading normals, facing back along the ray, mapped from -1..1 to 0..1 per axis.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn name(&self) -> &str {
+        "normals"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+        match scene.closest_hit(ray) {
+            Some(hit_info) => {
+                let normal = hit_info.normal * 0.5 + Vector3::repeat(0.5);
+                shown_as(Colour::new(normal[0], normal[1], normal[2]))
+            }
+            None => Colour::black()
+        }
+    }
+}
+
+/// Distance to the nearest object normalised over the scene's extent, white at the nearest point
+/// of it to the camera fading to black at the farthest. Anything further away, like a plane
+/// running off into the distance, is black. A scene with nothing but planes has no extent, and
+/// falls back on fading as 1 / (1 + distance).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Depth;
+
+impl Integrator for Depth {
+    fn name(&self) -> &str {
+        "depth"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+        match scene.closest_hit(ray) {
+            Some(hit_info) => {
+                let near = match depth_range(scene) {
+                    Some((nearest, farthest)) => {
+                        (1.0 - (hit_info.dist - nearest) / (farthest - nearest).max(f64::EPSILON)).clamp(0.0, 1.0)
+                    }
+                    None => 1.0 / (1.0 + hit_info.dist)
+                };
+                shown_as(Colour::new(near, near, near))
+            }
+            None => Colour::black()
+        }
+    }
+}
+
+/// How far the nearest and farthest points of the scene's extent are from the camera.
+fn depth_range(scene: &SceneState) -> Option<(f64, f64)> {
+    let extent = scene.extent()?;
+    let pos = scene.camera.pos;
+    let nearest = (pos.zip_zip_map(&extent.min, &extent.max, |axis, min, max| axis.clamp(min, max)) - pos).norm();
+    let farthest = (0..8)
+        .map(|corner: usize| {
+            let point = extent.min.zip_zip_map(&extent.max, &Vector3::new(1, 2, 4),
+                                               |min, max, bit| if corner & bit == 0 { min } else { max });
+            (point - pos).norm()
+        })
+        .fold(0.0, f64::max);
+    Some((nearest, farthest))
+}
+
+/// Texture coordinates as red and green, wrapping every whole unit. Triangles without any show
+/// their barycentric coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Uv;
+
+impl Integrator for Uv {
+    fn name(&self) -> &str {
+        "uv"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+        match scene.closest_hit(ray) {
+            Some(hit_info) => shown_as(Colour::new(hit_info.uv[0].rem_euclid(1.0), hit_info.uv[1].rem_euclid(1.0), 0.0)),
+            None => Colour::black()
+        }
+    }
+}
+
+/// What a `Heatmap` counts for each camera ray.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HeatmapCount {
+    /// Objects tested for a hit, scaled so testing every object is the hottest.
+    IntersectionTests,
+    /// BVH nodes visited, scaled so visiting every node is the hottest.
+    BvhNodes,
+}
+
+/// False colour of how much work finding each camera ray's closest hit took, on a log scale
+/// from dark blue for none through green to dark red for the worst case.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Heatmap {
+    pub count: HeatmapCount,
+}
+
+impl Integrator for Heatmap {
+    fn name(&self) -> &str {
+        match self.count {
+            HeatmapCount::IntersectionTests => "heatmap",
+            HeatmapCount::BvhNodes => "bvh-heatmap",
+        }
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+        let (_, stats) = scene.closest_hit_counted(ray);
+        let (count, worst) = match self.count {
+            HeatmapCount::IntersectionTests => (stats.intersection_tests, scene.geom().len()),
+            HeatmapCount::BvhNodes => (stats.nodes_visited, scene.bvh_node_count()),
+        };
+        let heat = if worst == 0 { 0.0 } else { (count as f64).ln_1p() / (worst as f64).ln_1p() };
+        shown_as(false_colour(heat.min(1.0)))
+    }
+}
+
+/// The jet colour map, dark blue through cyan, green and yellow to dark red as `heat` goes from 0
+/// to 1.
+fn false_colour(heat: f64) -> Colour {
+    let ramp = |centre: f64| (1.5 - (heat * 4.0 - centre).abs()).clamp(0.0, 1.0);
+    Colour::new(ramp(3.0), ramp(2.0), ramp(1.0))
+}
+
+/// Greys out another integrator's image and flags any samples that came out NaN or infinite in
+/// magenta, so a pixel is tinted by how many of its samples went wrong.
+pub struct NanCheck {
+    pub inner: Box<dyn Integrator>,
+}
+
+impl Integrator for NanCheck {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+        let colour = self.inner.radiance(scene, ray, aov, rng);
+        if !(colour.r.is_finite() && colour.g.is_finite() && colour.b.is_finite()) {
+            return NAN_FLAG;
+        }
+        let grey = (colour.r + colour.g + colour.b) / 3.0 * NAN_BACKDROP;
+        Colour::new(grey, grey, grey)
+    }
+}
+
+/// The linear colour that comes out as `display` once sRGB encoded, so debug values land in the
+/// image as they are with the default display settings.
+fn shown_as(display: Colour) -> Colour {
+    Colour::new(srgb_decode(display.r), srgb_decode(display.g), srgb_decode(display.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+
+    use crate::tracer::{Camera, Ray, SceneState};
+    use crate::tracer::aov::AovSample;
+    use crate::tracer::colour::Colour;
+    use crate::tracer::colour::tonemap::DisplayTransform;
+    use crate::tracer::debug::{Depth, Heatmap, HeatmapCount, NanCheck, Normals, Uv, false_colour, shown_as};
+    use crate::tracer::geom::Drawable;
+    use crate::tracer::geom::sphere::Sphere;
+    use crate::tracer::geom::triangle::Triangle;
+    use crate::tracer::integrator::Integrator;
+
+    struct Broken;
+
+    impl Integrator for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn radiance(&self, _scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+            //NaN to the right of the middle, fine everywhere else
+            if ray.dir()[0] > 0.0 { Colour::new(f64::NAN, 0.0, 0.0) } else { Colour::new(0.9, 0.6, 0.3) }
+        }
+    }
+
+    fn scene() -> SceneState {
+        let geom: Vec<Box<dyn Drawable>> = vec![
+            Box::new(Sphere::new(Vector3::new(0.0, 0.0, 4.0), 1.0, Colour::white(), 0.8, 0.2, 2.0)),
+            Box::new(Triangle::new(Vector3::new(-1.0, -1.0, 10.0), Vector3::new(5.0, -1.0, 10.0),
+                                   Vector3::new(-1.0, 5.0, 10.0), Colour::white(), 0.8, 0.2, 2.0)),
+        ];
+        let camera = Camera::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        SceneState::new(geom, vec![], camera, 0.1, Colour::black())
+    }
+
+    fn shade(integrator: &dyn Integrator, scene: &SceneState, dir: Vector3<f64>) -> [u8; 3] {
+        let colour = integrator.radiance(scene, &Ray::new(Vector3::zeros(), dir), None, &mut StdRng::seed_from_u64(0));
+        let pixel = DisplayTransform::default().to_display(colour);
+        [pixel.r, pixel.g, pixel.b]
+    }
+
+    #[test]
+    fn test_hit_views() {
+        let scene = scene();
+        let ahead = Vector3::new(0.0, 0.0, 1.0);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        //the front of the sphere faces straight back at the camera
+        assert_eq!(shade(&Normals, &scene, ahead), [128, 128, 0]);
+        //the front of the sphere is as near as the scene gets, the far corner of the triangle's
+        //bounds is root 150 away
+        assert_eq!(shade(&Depth, &scene, ahead), [255, 255, 255]);
+        let past_sphere = Vector3::new(0.25, 0.1, 1.0);
+        let near = 1.0 - (10.0 * past_sphere.norm() - 3.0) / (150.0_f64.sqrt() - 3.0);
+        let expected = DisplayTransform::default().to_display(shown_as(Colour::new(near, near, near)));
+        assert_eq!(shade(&Depth, &scene, past_sphere), [expected.r, expected.g, expected.b]);
+        //just past the sphere the triangle's barycentric weights of b and c, 7/12 and 1/3
+        assert_eq!(shade(&Uv, &scene, Vector3::new(0.25, 0.1, 1.0)), [149, 85, 0]);
+        for integrator in [&Normals as &dyn Integrator, &Depth, &Uv].iter() {
+            assert_eq!(shade(*integrator, &scene, up), [0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn test_heatmap() {
+        assert_eq!(false_colour(0.0), Colour::new(0.0, 0.0, 0.5));
+        assert_eq!(false_colour(0.5), Colour::new(0.5, 1.0, 0.5));
+        assert_eq!(false_colour(1.0), Colour::new(0.5, 0.0, 0.0));
+
+        //straight ahead the sphere hides the triangle so only one of the two is tested, and
+        //missing both their bounds tests neither
+        let scene = scene();
+        let tests = Heatmap { count: HeatmapCount::IntersectionTests };
+        let one_of_two = DisplayTransform::default().to_display(shown_as(false_colour(2.0_f64.ln() / 3.0_f64.ln())));
+        assert_eq!(shade(&tests, &scene, Vector3::new(0.0, 0.0, 1.0)), [one_of_two.r, one_of_two.g, one_of_two.b]);
+        assert_eq!(shade(&tests, &scene, Vector3::new(0.0, 1.0, 0.0)), [0, 0, 128]);
+        let nodes = Heatmap { count: HeatmapCount::BvhNodes };
+        assert_eq!(nodes.name(), "bvh-heatmap");
+        assert_ne!(shade(&nodes, &scene, Vector3::new(0.0, 0.0, 1.0)), shade(&nodes, &scene, Vector3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_nan_check() {
+        let scene = scene();
+        let check = NanCheck { inner: Box::new(Broken) };
+        assert_eq!(check.name(), "broken");
+        assert_eq!(shade(&check, &scene, Vector3::new(1.0, 0.0, 1.0)), [255, 0, 255]);
+        let [r, g, b] = shade(&check, &scene, Vector3::new(-1.0, 0.0, 1.0));
+        assert!(r == g && g == b && r > 0);
+    }
+}
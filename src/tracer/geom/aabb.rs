@@ -0,0 +1,127 @@
+use nalgebra::Vector3;
+
+use crate::tracer::Ray;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+}
+
+//Rounding error bound used to widen the slab test so a box never misses a ray that hits its contents
+const SLAB_SLACK: f64 = 1.0 + 6.0 * f64::EPSILON / (1.0 - 3.0 * f64::EPSILON);
+
+impl Aabb {
+    pub fn new(min: Vector3<f64>, max: Vector3<f64>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// An inverted box that any union or grow will replace.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::repeat(f64::INFINITY),
+            max: Vector3::repeat(f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<'a, I>(points: I) -> Aabb where I: IntoIterator<Item = &'a Vector3<f64>> {
+        points.into_iter().fold(Aabb::empty(), |bounds, point| bounds.grow(point))
+    }
+
+    pub fn grow(&self, point: &Vector3<f64>) -> Aabb {
+        Aabb {
+            min: self.min.zip_map(point, f64::min),
+            max: self.max.zip_map(point, f64::max),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.zip_map(&other.min, f64::min),
+            max: self.max.zip_map(&other.max, f64::max),
+        }
+    }
+
+    /// True for the inverted box from `empty` that hasn't had anything added.
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn contains(&self, point: &Vector3<f64>) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    /// Size of the box along each axis.
+    pub fn extent(&self) -> Vector3<f64> {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Vector3<f64> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extent = self.extent();
+        2.0 * (extent[0] * extent[1] + extent[1] * extent[2] + extent[2] * extent[0])
+    }
+
+    /// Slab test, gives the distance the ray enters the box if that is before `t_max`.
+    pub fn hit(&self, ray: &Ray, inv_dir: &Vector3<f64>, t_max: f64) -> Option<f64> {
+        let mut t_near: f64 = 0.0;
+        let mut t_far = t_max;
+        for axis in 0..3 {
+            let t_min_plane = (self.min[axis] - ray.orig[axis]) * inv_dir[axis];
+            let t_max_plane = (self.max[axis] - ray.orig[axis]) * inv_dir[axis];
+            let (t_enter, t_exit) = if t_min_plane <= t_max_plane {
+                (t_min_plane, t_max_plane)
+            } else {
+                (t_max_plane, t_min_plane)
+            };
+            //max/min drop the NaNs a ray lying in a slab's plane produces
+            t_near = t_near.max(t_enter);
+            t_far = t_far.min(t_exit * SLAB_SLACK);
+            if t_near > t_far {
+                return None;
+            }
+        }
+        Some(t_near)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use crate::tracer::geom::aabb::Aabb;
+    use crate::tracer::Ray;
+
+    #[test]
+    fn test_hit() {
+        let bounds = Aabb::new(Vector3::new(-1.0, -1.0, 2.0), Vector3::new(1.0, 1.0, 4.0));
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let inv_dir = ray.dir.map(|axis| 1.0 / axis);
+        assert_eq!(bounds.hit(&ray, &inv_dir, f64::INFINITY), Some(2.0));
+        assert_eq!(bounds.hit(&ray, &inv_dir, 1.0), None);
+
+        let miss = Ray::new(Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(bounds.hit(&miss, &miss.dir.map(|axis| 1.0 / axis), f64::INFINITY), None);
+    }
+
+    #[test]
+    fn test_union_and_area() {
+        let a = Aabb::from_points(&[Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0)]);
+        let b = Aabb::new(Vector3::new(2.0, 0.0, 0.0), Vector3::new(3.0, 1.0, 1.0));
+        let both = a.union(&b);
+        assert_eq!(both.min, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(both.max, Vector3::new(3.0, 1.0, 1.0));
+        assert_eq!(both.surface_area(), 14.0);
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+        assert!(Aabb::empty().is_empty());
+        assert!(!both.is_empty());
+        assert!(both.contains(&Vector3::new(2.5, 0.5, 1.0)));
+        assert!(!both.contains(&Vector3::new(2.5, 1.5, 1.0)));
+    }
+}
@@ -0,0 +1,260 @@
+use nalgebra::Vector3;
+
+use crate::tracer::geom::aabb::Aabb;
+use crate::tracer::Ray;
+
+//Number of buckets centroids are binned into when costing splits
+const SAH_BUCKETS: usize = 12;
+//Relative cost of visiting a node compared to testing a primitive
+const TRAVERSAL_COST: f64 = 0.5;
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug)]
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    //The left child always directly follows its parent
+    Interior { right: usize },
+}
+
+#[derive(Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+/// Bounding volume hierarchy over a set of boxes, built with the surface area heuristic. It only
+/// knows about item indices, callers test the items themselves as traversal reaches them.
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    items: Vec<usize>,
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector3<f64>,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut build_items: Vec<BuildItem> = bounds.iter().enumerate()
+            .map(|(index, bounds)| BuildItem { index, bounds: *bounds, centroid: bounds.centroid() })
+            .collect();
+        let mut bvh = Bvh { nodes: Vec::with_capacity(bounds.len() * 2), items: Vec::with_capacity(bounds.len()) };
+        if !build_items.is_empty() {
+            bvh.build_node(&mut build_items);
+        }
+        bvh
+    }
+
+    /// Bounds of everything in the hierarchy, `None` when it's empty.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bounds)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn build_node(&mut self, items: &mut [BuildItem]) -> usize {
+        let bounds = items.iter().fold(Aabb::empty(), |all, item| all.union(&item.bounds));
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bounds, kind: NodeKind::Leaf { first: 0, count: 0 } });
+
+        let split = if items.len() > 1 { find_split(items, &bounds) } else { None };
+        match split {
+            Some(mid) => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build_node(left);
+                let right = self.build_node(right);
+                self.nodes[node_index].kind = NodeKind::Interior { right };
+            }
+            None => {
+                let first = self.items.len();
+                self.items.extend(items.iter().map(|item| item.index));
+                self.nodes[node_index].kind = NodeKind::Leaf { first, count: items.len() };
+            }
+        }
+        node_index
+    }
+
+    /// Walks the nodes the ray passes through nearest first. `test` is given an item and the
+    /// closest hit so far, and returns the distance of a new closest hit on that item, if any.
+    /// Boxes entered exactly at the closest distance are still visited so callers can break ties.
+    /// Returns how many nodes were visited.
+    pub fn traverse<F>(&self, ray: &Ray, t_max: f64, mut test: F) -> usize where F: FnMut(usize, f64) -> Option<f64> {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+        let inv_dir = ray.dir.map(|axis| 1.0 / axis);
+        let mut closest = t_max;
+        let mut visited = 0;
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(node_index) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[node_index];
+            match node.bounds.hit(ray, &inv_dir, closest) {
+                Some(_) => {}
+                None => continue
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for item in &self.items[first..first + count] {
+                        if let Some(dist) = test(*item, closest) {
+                            closest = dist;
+                        }
+                    }
+                }
+                NodeKind::Interior { right } => {
+                    let left = node_index + 1;
+                    let left_hit = self.nodes[left].bounds.hit(ray, &inv_dir, closest);
+                    let right_hit = self.nodes[right].bounds.hit(ray, &inv_dir, closest);
+                    match (left_hit, right_hit) {
+                        (Some(left_dist), Some(right_dist)) if right_dist < left_dist => {
+                            stack.push(left);
+                            stack.push(right);
+                        }
+                        (Some(_), Some(_)) => {
+                            stack.push(right);
+                            stack.push(left);
+                        }
+                        (Some(_), None) => stack.push(left),
+                        (None, Some(_)) => stack.push(right),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        visited
+    }
+
+    /// Stops at the first item `test` says blocks the ray, for shadow rays.
+    pub fn any_hit<F>(&self, ray: &Ray, t_max: f64, mut test: F) -> bool where F: FnMut(usize) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv_dir = ray.dir.map(|axis| 1.0 / axis);
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds.hit(ray, &inv_dir, t_max).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    if self.items[first..first + count].iter().any(|item| test(*item)) {
+                        return true;
+                    }
+                }
+                NodeKind::Interior { right } => {
+                    stack.push(right);
+                    stack.push(node_index + 1);
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Bins the items' centroids along each axis and picks the cheapest split by surface area
+/// heuristic, partitioning `items` around it. `None` means a leaf is cheaper.
+fn find_split(items: &mut [BuildItem], bounds: &Aabb) -> Option<usize> {
+    let centroid_bounds = items.iter().fold(Aabb::empty(), |all, item| all.grow(&item.centroid));
+    let leaf_cost = items.len() as f64;
+    let parent_area = bounds.surface_area();
+    let mut best: Option<(f64, usize, usize)> = None;
+
+    for axis in 0..3 {
+        let axis_min = centroid_bounds.min[axis];
+        let axis_extent = centroid_bounds.max[axis] - axis_min;
+        if axis_extent <= 0.0 {
+            continue;
+        }
+        let mut bucket_counts = [0usize; SAH_BUCKETS];
+        let mut bucket_bounds = [Aabb::empty(); SAH_BUCKETS];
+        for item in items.iter() {
+            let bucket = bucket_of(item.centroid[axis], axis_min, axis_extent);
+            bucket_counts[bucket] += 1;
+            bucket_bounds[bucket] = bucket_bounds[bucket].union(&item.bounds);
+        }
+        for split in 1..SAH_BUCKETS {
+            let (left_count, left_bounds) = (0..split).fold((0, Aabb::empty()), |(count, all), bucket| {
+                (count + bucket_counts[bucket], all.union(&bucket_bounds[bucket]))
+            });
+            let (right_count, right_bounds) = (split..SAH_BUCKETS).fold((0, Aabb::empty()), |(count, all), bucket| {
+                (count + bucket_counts[bucket], all.union(&bucket_bounds[bucket]))
+            });
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = if parent_area > 0.0 {
+                TRAVERSAL_COST + (left_count as f64 * left_bounds.surface_area()
+                    + right_count as f64 * right_bounds.surface_area()) / parent_area
+            } else {
+                TRAVERSAL_COST + leaf_cost / 2.0
+            };
+            match best {
+                Some((best_cost, _, _)) if best_cost <= cost => {}
+                _ => best = Some((cost, axis, split))
+            }
+        }
+    }
+
+    let (cost, axis, split) = best?;
+    if cost >= leaf_cost && items.len() <= MAX_LEAF_SIZE {
+        return None;
+    }
+    let axis_min = centroid_bounds.min[axis];
+    let axis_extent = centroid_bounds.max[axis] - axis_min;
+    //Stable partition so items with the same bucket keep their relative order
+    items.sort_by_key(|item| bucket_of(item.centroid[axis], axis_min, axis_extent) >= split);
+    items.iter().position(|item| bucket_of(item.centroid[axis], axis_min, axis_extent) >= split)
+}
+
+fn bucket_of(centroid: f64, axis_min: f64, axis_extent: f64) -> usize {
+    (((centroid - axis_min) / axis_extent * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use crate::tracer::geom::aabb::Aabb;
+    use crate::tracer::geom::bvh::Bvh;
+    use crate::tracer::Ray;
+
+    #[test]
+    fn test_traverse_finds_every_box_hit() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let boxes: Vec<Aabb> = (0..200).map(|_| {
+            let centre = Vector3::new(rng.gen_range(-10.0, 10.0), rng.gen_range(-10.0, 10.0), rng.gen_range(0.0, 20.0));
+            Aabb::new(centre - Vector3::repeat(0.5), centre + Vector3::repeat(0.5))
+        }).collect();
+        let bvh = Bvh::build(&boxes);
+        assert!(bvh.node_count() > 1);
+
+        for _ in 0..100 {
+            let ray = Ray::new(Vector3::zeros(), Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 1.0));
+            let inv_dir = ray.dir.map(|axis| 1.0 / axis);
+            let mut expected: Vec<usize> = (0..boxes.len())
+                .filter(|&i| boxes[i].hit(&ray, &inv_dir, f64::INFINITY).is_some())
+                .collect();
+            let mut found = vec![];
+            let visited = bvh.traverse(&ray, f64::INFINITY, |i, _| {
+                if boxes[i].hit(&ray, &inv_dir, f64::INFINITY).is_some() {
+                    found.push(i);
+                }
+                None
+            });
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected);
+            assert!(visited >= 1 && visited <= bvh.node_count());
+            assert_eq!(bvh.any_hit(&ray, f64::INFINITY, |i| boxes[i].hit(&ray, &inv_dir, f64::INFINITY).is_some()),
+                       !expected.is_empty());
+        }
+    }
+}
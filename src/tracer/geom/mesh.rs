@@ -0,0 +1,203 @@
+use nalgebra::{Matrix3, Matrix4, Point3, U3, Vector2, Vector3};
+
+use crate::tracer::colour::Material;
+use crate::tracer::geom::{Intersects, MaterialAt, Drawable, Bounded, Hit};
+use crate::tracer::geom::aabb::Aabb;
+use crate::tracer::geom::bvh::Bvh;
+use crate::tracer::geom::triangle::intersect_triangle;
+use crate::tracer::Ray;
+
+/// Indexed triangle mesh. Triangles index into shared vertex buffers, per vertex normals give
+/// smooth shading when present and UVs are carried along for texturing. The triangles get their
+/// own BVH so a ray only tests the few near it, which is why the positions and triangles can
+/// only be changed through `transform`.
+pub struct TriangleMesh {
+    positions: Vec<Vector3<f64>>,
+    pub normals: Option<Vec<Vector3<f64>>>,
+    pub uvs: Option<Vec<Vector2<f64>>>,
+    indices: Vec<[usize; 3]>,
+    pub material: Material,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vector3<f64>>, normals: Option<Vec<Vector3<f64>>>, uvs: Option<Vec<Vector2<f64>>>,
+               indices: Vec<[usize; 3]>, material: Material) -> TriangleMesh {
+        let bvh = Bvh::build(&triangle_bounds(&positions, &indices));
+        TriangleMesh {
+            positions,
+            normals: normals.map(|normals| normals.into_iter().map(|normal| normal.normalize()).collect()),
+            uvs,
+            indices,
+            material,
+            bvh
+        }
+    }
+
+    pub fn positions(&self) -> &[Vector3<f64>] {
+        &self.positions
+    }
+
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+
+    /// Moves the mesh's vertices by an affine transform, normals go through the inverse
+    /// transpose so they stay perpendicular under non-uniform scaling.
+    pub fn transform(&mut self, transform: &Matrix4<f64>) {
+        for position in &mut self.positions {
+            *position = transform.transform_point(&Point3::from(*position)).coords;
+        }
+        if let Some(normals) = &mut self.normals {
+            let linear: Matrix3<f64> = transform.fixed_slice::<U3, U3>(0, 0).into_owned();
+            let normal_matrix = linear.try_inverse().unwrap_or(linear).transpose();
+            for normal in normals {
+                *normal = (normal_matrix * *normal).normalize();
+            }
+        }
+        self.bvh = Bvh::build(&triangle_bounds(&self.positions, &self.indices));
+    }
+
+    fn hit_on(&self, ray: &Ray, index: usize, dist: f64, u: f64, v: f64) -> Hit {
+        let [i0, i1, i2] = self.indices[index];
+        let (a, b, c) = (&self.positions[i0], &self.positions[i1], &self.positions[i2]);
+        let mut normal = (b - a).cross(&(c - a)).normalize();
+        let shading_normal = match &self.normals {
+            Some(normals) => {
+                let shading_normal = (normals[i0] * (1.0 - u - v) + normals[i1] * u + normals[i2] * v).normalize();
+                // trust the vertex normals over the winding order for which side is the front
+                if normal.dot(&shading_normal) < 0.0 {
+                    normal = -normal;
+                }
+                shading_normal
+            }
+            None => normal
+        };
+        let uv = match &self.uvs {
+            Some(uvs) => uvs[i0] * (1.0 - u - v) + uvs[i1] * u + uvs[i2] * v,
+            None => Vector2::new(u, v)
+        };
+        Hit::new(ray, dist, normal, shading_normal, uv, index)
+    }
+}
+
+fn triangle_bounds(positions: &[Vector3<f64>], indices: &[[usize; 3]]) -> Vec<Aabb> {
+    indices.iter()
+        .map(|&[i0, i1, i2]| Aabb::from_points(&[positions[i0], positions[i1], positions[i2]]))
+        .collect()
+}
+
+impl MaterialAt for TriangleMesh {
+    fn material_at(&self, _hit:&Vector3<f64>) -> &Material {
+        &self.material
+    }
+}
+
+impl Intersects for TriangleMesh {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let mut closest: Option<(f64, f64, f64, usize)> = None;
+        self.bvh.traverse(ray, t_max, |index, closest_dist| {
+            let [i0, i1, i2] = self.indices[index];
+            let (dist, u, v) = intersect_triangle(ray, &self.positions[i0], &self.positions[i1], &self.positions[i2],
+                                                  t_min, closest_dist)?;
+            match closest {
+                //ties go to the earlier triangle, the same as testing them in order
+                Some((closest_dist, _, _, closest_index))
+                    if closest_dist < dist || (closest_dist == dist && closest_index < index) => None,
+                _ => {
+                    closest = Some((dist, u, v, index));
+                    Some(dist)
+                }
+            }
+        });
+        closest.map(|(dist, u, v, index)| self.hit_on(ray, index, dist, u, v))
+    }
+}
+
+impl Bounded for TriangleMesh {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.positions))
+    }
+}
+
+impl Drawable for TriangleMesh {}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use approx;
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+    use crate::tracer::colour::{Colour, Material};
+    use crate::tracer::geom::mesh::TriangleMesh;
+    use crate::tracer::geom::triangle::intersect_triangle;
+    use crate::tracer::geom::Intersects;
+    use crate::tracer::Ray;
+
+    fn quad(normals: Option<Vec<Vector3<f64>>>) -> TriangleMesh {
+        TriangleMesh::new(
+            vec![Vector3::new(-1.0, -1.0, 3.0), Vector3::new(1.0, -1.0, 3.0),
+                 Vector3::new(1.0, 1.0, 3.0), Vector3::new(-1.0, 1.0, 3.0)],
+            normals,
+            None,
+            vec![[0, 1, 2], [0, 2, 3]],
+            Material::new(Colour::black(), 0.6, 0.4, 2.0)
+        )
+    }
+
+    #[test]
+    fn test_intersects() {
+        let mesh = quad(None);
+        let ray = Ray{
+            orig: Vector3::new(-0.5, 0.5, 0.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        let hit = mesh.intersect(&ray, 0.0001, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 3.0, max_ulps = 3);
+        approx::assert_ulps_eq!(-1.0, hit.facing_normal()[2], max_ulps = 3);
+        assert_eq!(hit.primitive_id, 1);
+
+        let miss = Ray{
+            orig: Vector3::new(1.5, 0.0, 0.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        assert!(mesh.intersect(&miss, 0.0001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        let tilted = Vector3::new(1.0, 0.0, -1.0);
+        let straight = Vector3::new(0.0, 0.0, -1.0);
+        let mesh = quad(Some(vec![straight, tilted, tilted, straight]));
+        let ray = Ray{
+            orig: Vector3::new(0.0, -0.5, 0.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        let norm = mesh.intersect(&ray, 0.0001, f64::INFINITY).unwrap().shading_normal;
+        assert!(norm[0] > 0.0 && norm[0] < tilted.normalize()[0]);
+        approx::assert_ulps_eq!(1.0, norm.magnitude(), max_ulps = 3);
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_search() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let positions: Vec<Vector3<f64>> = (0..300)
+            .map(|_| Vector3::new(rng.gen_range(-2.0, 2.0), rng.gen_range(-2.0, 2.0), rng.gen_range(2.0, 6.0)))
+            .collect();
+        let indices: Vec<[usize; 3]> = (0..100).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+        let mesh = TriangleMesh::new(positions.clone(), None, None, indices.clone(),
+                                     Material::new(Colour::black(), 0.6, 0.4, 2.0));
+
+        for _ in 0..200 {
+            let ray = Ray::new(Vector3::zeros(), Vector3::new(rng.gen_range(-0.5, 0.5), rng.gen_range(-0.5, 0.5), 1.0));
+            let linear = indices.iter()
+                .filter_map(|&[i0, i1, i2]| intersect_triangle(&ray, &positions[i0], &positions[i1], &positions[i2],
+                                                               0.0001, f64::INFINITY))
+                .map(|(dist, _, _)| dist)
+                .fold(None, |closest: Option<f64>, dist| Some(closest.map_or(dist, |closest| closest.min(dist))));
+            let dist = mesh.intersect(&ray, 0.0001, f64::INFINITY).map(|hit| hit.dist);
+            assert_eq!(dist, linear);
+        }
+    }
+}
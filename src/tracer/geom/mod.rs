@@ -1,19 +1,70 @@
 pub mod sphere;
 pub mod plane;
+pub mod triangle;
+pub mod mesh;
+pub mod aabb;
+pub mod bvh;
 
 
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3};
 
 use crate::tracer::colour::Material;
+use crate::tracer::geom::aabb::Aabb;
 use crate::tracer::Ray;
 
 
+/// Everything known about where a ray met a surface.
+#[derive(Debug, Copy, Clone)]
+pub struct Hit {
+    pub dist: f64,
+    pub point: Vector3<f64>,
+    /// The surface's own normal, outwards for closed shapes, whichever side the ray came from.
+    pub normal: Vector3<f64>,
+    /// Normal to light with, interpolated across smooth meshes, on the same side as `normal`.
+    pub shading_normal: Vector3<f64>,
+    pub uv: Vector2<f64>,
+    /// Whether the ray arrived on the side `normal` points to.
+    pub front_face: bool,
+    /// Which part of the object was hit, the triangle index for meshes and 0 for simple shapes.
+    pub primitive_id: usize,
+}
+
+impl Hit {
+    pub fn new(ray: &Ray, dist: f64, normal: Vector3<f64>, shading_normal: Vector3<f64>, uv: Vector2<f64>,
+               primitive_id: usize) -> Hit {
+        Hit {
+            dist,
+            point: ray.point_along(dist),
+            normal,
+            shading_normal,
+            uv,
+            front_face: ray.dir.dot(&normal) < 0.0,
+            primitive_id,
+        }
+    }
+
+    /// The shading normal flipped if needed to face back along the ray.
+    pub fn facing_normal(&self) -> Vector3<f64> {
+        if self.front_face {
+            self.shading_normal
+        } else {
+            -self.shading_normal
+        }
+    }
+}
+
 pub trait Intersects {
-    fn intersect(&self, ray: &Ray) -> (f64, Option<Vector3<f64>>);
+    /// The nearest hit with a distance along the ray within `[t_min, t_max]`.
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit>;
 }
 
 pub trait MaterialAt {
     fn material_at(&self, hit_point:&Vector3<f64>) -> &Material;
 }
 
-pub trait Drawable: MaterialAt + Intersects {}
\ No newline at end of file
+pub trait Bounded {
+    /// `None` for infinite primitives like planes, which spatial structures have to test separately.
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+pub trait Drawable: MaterialAt + Intersects + Bounded + Send + Sync {}
\ No newline at end of file
@@ -1,7 +1,8 @@
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3};
 
-use crate::tracer::colour::{RGB, Material};
-use crate::tracer::geom::{Intersects, MaterialAt, Drawable};
+use crate::tracer::colour::{Colour, Material};
+use crate::tracer::geom::{Intersects, MaterialAt, Drawable, Bounded, Hit};
+use crate::tracer::geom::aabb::Aabb;
 use crate::tracer::Ray;
 
 pub struct Plane {
@@ -11,16 +12,11 @@ pub struct Plane {
 }
 
 impl Plane {
-    pub fn new(point: Vector3<f64>, norm: Vector3<f64>, colour: RGB, diffuse:f64, specular:f64, specular_exp:f64) -> Plane {
+    pub fn new(point: Vector3<f64>, norm: Vector3<f64>, colour: Colour, diffuse:f64, specular:f64, specular_exp:f64) -> Plane {
         Plane {
             point,
             norm: norm.normalize(),
-            material: Material {
-                rgb: colour,
-                diffuse,
-                specular,
-                specular_exp
-            }
+            material: Material::new(colour, diffuse, specular, specular_exp)
         }
     }
 }
@@ -32,15 +28,28 @@ impl MaterialAt for Plane {
 }
 
 impl Intersects for Plane {
-    fn intersect(&self, ray: &Ray) -> (f64,  Option<Vector3<f64>>) {
-        let orig_to_point = &self.point - &ray.orig;
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let orig_to_point = self.point - ray.orig;
         let denom = self.norm.dot(&ray.dir);
         let d = orig_to_point.dot(&self.norm) / denom;
-        if d > 0.0001 {
-            (d, Some(self.norm.clone()))
-        } else {
-            (-1.0,  None)
+        // also rules out the NaN from a ray running along the plane
+        if !(t_min..=t_max).contains(&d) {
+            return None;
         }
+        let hit_point = ray.point_along(d);
+        // uvs are world distances along two axes in the plane
+        let helper = if self.norm[0].abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+        let tangent = helper.cross(&self.norm).normalize();
+        let bitangent = self.norm.cross(&tangent);
+        let offset = hit_point - self.point;
+        let uv = Vector2::new(offset.dot(&tangent), offset.dot(&bitangent));
+        Some(Hit::new(ray, d, self.norm, self.norm, uv, 0))
+    }
+}
+
+impl Bounded for Plane {
+    fn bounds(&self) -> Option<Aabb> {
+        None
     }
 }
 
@@ -51,26 +60,40 @@ impl Drawable for Plane {}
 mod tests {
     use nalgebra::Vector3;
     use approx;
-    use crate::tracer::colour::RGB;
+    use crate::tracer::colour::Colour;
     use crate::tracer::geom::plane::Plane;
-    use crate::tracer::geom::Intersects;
+    use crate::tracer::geom::{Bounded, Intersects};
     use crate::tracer::Ray;
 
 
     #[test]
     fn test_intersects() {
-        let sphere = Plane::new(Vector3::new(0.0,0.0,3.0), Vector3::new(0.0,0.0,-1.0), RGB{r:0,g:0,b:0},
+        let sphere = Plane::new(Vector3::new(0.0,0.0,3.0), Vector3::new(0.0,0.0,-1.0), Colour::black(),
         0.6,0.4, 2.0);
         let ray = Ray{
             orig: Vector3::new(0.0,0.0,0.0),
             dir: Vector3::new(0.0, 0.0, 1.0)
         };
-        let (dist,norm) = sphere.intersect(&ray);
-        approx::assert_ulps_eq!(dist, 3.0, max_ulps =  3);
-        approx::assert_ulps_eq!(0.0, norm.unwrap()[0],max_ulps = 3);
-        approx::assert_ulps_eq!(0.0, norm.unwrap()[1],max_ulps = 3);
-        approx::assert_ulps_eq!(-1.0, norm.unwrap()[2],max_ulps = 3);
-        approx::assert_ulps_eq!(1.0, norm.unwrap().magnitude() , max_ulps=3);
+        let hit = sphere.intersect(&ray, 0.0001, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 3.0, max_ulps =  3);
+        approx::assert_ulps_eq!(0.0, hit.normal[0],max_ulps = 3);
+        approx::assert_ulps_eq!(0.0, hit.normal[1],max_ulps = 3);
+        approx::assert_ulps_eq!(-1.0, hit.normal[2],max_ulps = 3);
+        approx::assert_ulps_eq!(1.0, hit.normal.magnitude() , max_ulps=3);
+        assert!(hit.front_face);
+
+        let parallel = Ray{
+            orig: Vector3::new(0.0,0.0,0.0),
+            dir: Vector3::new(1.0, 0.0, 0.0)
+        };
+        assert!(sphere.intersect(&parallel, 0.0001, f64::INFINITY).is_none());
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_unbounded() {
+        let plane = Plane::new(Vector3::new(0.0,0.0,3.0), Vector3::new(0.0,0.0,-1.0), Colour::black(),
+        0.6,0.4, 2.0);
+        assert!(plane.bounds().is_none());
+    }
+
+}
@@ -1,7 +1,10 @@
-use nalgebra::Vector3;
+use std::f64::consts::PI;
 
-use crate::tracer::colour::{RGB, Material};
-use crate::tracer::geom::{Intersects, MaterialAt, Drawable};
+use nalgebra::{Vector2, Vector3};
+
+use crate::tracer::colour::{Colour, Material};
+use crate::tracer::geom::{Intersects, MaterialAt, Drawable, Bounded, Hit};
+use crate::tracer::geom::aabb::Aabb;
 use crate::tracer::Ray;
 
 pub struct Sphere {
@@ -11,16 +14,11 @@ pub struct Sphere {
 }
 
 impl Sphere {
-    pub fn new(pos: Vector3<f64>, radius: f64, colour: RGB, diffuse:f64, specular:f64, specular_exp:f64) -> Sphere {
+    pub fn new(pos: Vector3<f64>, radius: f64, colour: Colour, diffuse:f64, specular:f64, specular_exp:f64) -> Sphere {
         Sphere {
             pos,
             radius,
-            material: Material {
-                rgb: colour,
-                diffuse,
-                specular,
-                specular_exp
-            }
+            material: Material::new(colour, diffuse, specular, specular_exp)
         }
     }
 }
@@ -32,26 +30,40 @@ impl MaterialAt for Sphere {
 }
 
 impl Intersects for Sphere {
-    fn intersect(&self, ray: &Ray) -> (f64,  Option<Vector3<f64>>) {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         // the vector from the ray origin (camera origin) to the centre of the sphere.
-        let orig_to_loc = &ray.orig - &self.pos;
+        let orig_to_loc = ray.orig - self.pos;
         // normal quadratic abc
         let a = ray.dir.dot(&ray.dir);
         let b = 2.0 * orig_to_loc.dot(&ray.dir);
         let c = orig_to_loc.dot(&orig_to_loc) - self.radius*self.radius;
         let discriminant =  b*b - 4.0*a*c;
-        return if discriminant < 0.0 {
-            (-1.0,  None)
-        } else {
-            let numerator = -b - discriminant.sqrt();
-            if numerator > 0.0 {
-                let dist = numerator / (2.0 * a);
-                (dist, Some((&ray.point_along(dist)  - &self.pos).normalize()))
-            }
-            else {
-                (-1.0, None)
-            }
+        if discriminant < 0.0 {
+            return None;
         }
+        // near root first, the far one is where a ray starting inside the sphere leaves it
+        let near = (-b - discriminant.sqrt()) / (2.0 * a);
+        let far = (-b + discriminant.sqrt()) / (2.0 * a);
+        let dist = if (t_min..=t_max).contains(&near) {
+            near
+        } else if (t_min..=t_max).contains(&far) {
+            far
+        } else {
+            return None;
+        };
+        let normal = (ray.point_along(dist) - self.pos).normalize();
+        let uv = Vector2::new(
+            0.5 + normal[2].atan2(normal[0]) / (2.0 * PI),
+            normal[1].clamp(-1.0, 1.0).acos() / PI,
+        );
+        Some(Hit::new(ray, dist, normal, normal, uv, 0))
+    }
+}
+
+impl Bounded for Sphere {
+    fn bounds(&self) -> Option<Aabb> {
+        let radius = Vector3::repeat(self.radius);
+        Some(Aabb::new(self.pos - radius, self.pos + radius))
     }
 }
 
@@ -62,39 +74,65 @@ impl Drawable for Sphere {}
 mod tests {
     use nalgebra::Vector3;
     use approx;
-    use crate::tracer::colour::RGB;
+    use crate::tracer::colour::Colour;
     use crate::tracer::geom::sphere::Sphere;
-    use crate::tracer::geom::Intersects;
+    use crate::tracer::geom::{Bounded, Intersects};
     use crate::tracer::Ray;
 
 
     #[test]
     fn test_intersects() {
-        let sphere = Sphere::new(Vector3::new(0.0,0.0,3.0), 1.0, RGB{r:0,g:0,b:0},
+        let sphere = Sphere::new(Vector3::new(0.0,0.0,3.0), 1.0, Colour::black(),
         0.6,0.4,2.0);
         let ray = Ray{
             orig: Vector3::new(0.0,0.0,0.0),
             dir: Vector3::new(0.0, 0.0, 1.0)
         };
-        let (dist,norm) = sphere.intersect(&ray);
-        approx::assert_ulps_eq!(dist, 2.0, max_ulps =  3);
-        approx::assert_ulps_eq!(0.0, norm.unwrap()[0],max_ulps = 3);
-        approx::assert_ulps_eq!(0.0, norm.unwrap()[1],max_ulps = 3);
-        approx::assert_ulps_eq!(-1.0, norm.unwrap()[2],max_ulps = 3);
-        approx::assert_ulps_eq!(1.0, norm.unwrap().magnitude() , max_ulps=3);
-
-        let sphere = Sphere::new(Vector3::new(2.0,2.0,0.0), 1.0, RGB{r:0,g:0,b:0},
+        let hit = sphere.intersect(&ray, 0.0, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 2.0, max_ulps =  3);
+        approx::assert_ulps_eq!(0.0, hit.normal[0],max_ulps = 3);
+        approx::assert_ulps_eq!(0.0, hit.normal[1],max_ulps = 3);
+        approx::assert_ulps_eq!(-1.0, hit.normal[2],max_ulps = 3);
+        approx::assert_ulps_eq!(1.0, hit.normal.magnitude() , max_ulps=3);
+        assert!(hit.front_face);
+        assert!(sphere.intersect(&ray, 0.0, 1.5).is_none());
+
+        let sphere = Sphere::new(Vector3::new(2.0,2.0,0.0), 1.0, Colour::black(),
         0.6,0.4,2.0);
         let ray = Ray{
             orig: Vector3::new(0.0,0.0,0.0),
             dir: Vector3::new(1.0, 1.0, 0.0)
         };
         let angle = (45.0_f64).to_radians();
-        let (dist,norm) = sphere.intersect(&ray);
-        approx::assert_ulps_eq!(dist, 2.0 - angle.cos(), max_ulps =  3);
-        approx::assert_ulps_eq!( -angle.cos(), norm.unwrap()[0],max_ulps = 3);
-        approx::assert_ulps_eq!(-angle.cos(), norm.unwrap()[1],max_ulps = 3);
-        approx::assert_ulps_eq!(0.0, norm.unwrap()[2],max_ulps = 3);
+        let hit = sphere.intersect(&ray, 0.0, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 2.0 - angle.cos(), max_ulps =  3);
+        approx::assert_ulps_eq!( -angle.cos(), hit.normal[0],max_ulps = 3);
+        approx::assert_ulps_eq!(-angle.cos(), hit.normal[1],max_ulps = 3);
+        approx::assert_ulps_eq!(0.0, hit.normal[2],max_ulps = 3);
+    }
+
+    #[test]
+    fn test_intersects_from_inside() {
+        let sphere = Sphere::new(Vector3::new(0.0,0.0,3.0), 1.0, Colour::black(),
+        0.6,0.4,2.0);
+        let ray = Ray{
+            orig: Vector3::new(0.0,0.0,3.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        let hit = sphere.intersect(&ray, 0.0001, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 1.0, max_ulps =  3);
+        approx::assert_ulps_eq!(1.0, hit.normal[2],max_ulps = 3);
+        assert!(!hit.front_face);
+        approx::assert_ulps_eq!(-1.0, hit.facing_normal()[2],max_ulps = 3);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_bounds() {
+        let sphere = Sphere::new(Vector3::new(1.0,2.0,3.0), 0.5, Colour::black(),
+        0.6,0.4,2.0);
+        let bounds = sphere.bounds().unwrap();
+        assert_eq!(bounds.min, Vector3::new(0.5, 1.5, 2.5));
+        assert_eq!(bounds.max, Vector3::new(1.5, 2.5, 3.5));
+    }
+
+}
@@ -0,0 +1,117 @@
+use nalgebra::{Vector2, Vector3};
+
+use crate::tracer::colour::{Colour, Material};
+use crate::tracer::geom::{Intersects, MaterialAt, Drawable, Bounded, Hit};
+use crate::tracer::geom::aabb::Aabb;
+use crate::tracer::Ray;
+
+//Below this the ray is treated as parallel to the triangle
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+pub struct Triangle {
+    pub a: Vector3<f64>,
+    pub b: Vector3<f64>,
+    pub c: Vector3<f64>,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3<f64>, b: Vector3<f64>, c: Vector3<f64>, colour: Colour, diffuse:f64, specular:f64, specular_exp:f64) -> Triangle {
+        Triangle {
+            a,
+            b,
+            c,
+            material: Material::new(colour, diffuse, specular, specular_exp)
+        }
+    }
+}
+
+/// Möller–Trumbore ray/triangle test, gives the distance along the ray and the barycentric
+/// weights of `b` and `c` at the hit.
+pub(crate) fn intersect_triangle(ray: &Ray, a: &Vector3<f64>, b: &Vector3<f64>, c: &Vector3<f64>,
+                                 t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge_ab = b - a;
+    let edge_ac = c - a;
+    let p = ray.dir.cross(&edge_ac);
+    let det = edge_ab.dot(&p);
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let a_to_orig = ray.orig - a;
+    let u = a_to_orig.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = a_to_orig.cross(&edge_ab);
+    let v = ray.dir.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let dist = edge_ac.dot(&q) * inv_det;
+    if (t_min..=t_max).contains(&dist) {
+        Some((dist, u, v))
+    } else {
+        None
+    }
+}
+
+impl MaterialAt for Triangle {
+    fn material_at(&self, _hit:&Vector3<f64>) -> &Material {
+        &self.material
+    }
+}
+
+impl Intersects for Triangle {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (dist, u, v) = intersect_triangle(ray, &self.a, &self.b, &self.c, t_min, t_max)?;
+        let normal = (self.b - self.a).cross(&(self.c - self.a)).normalize();
+        Some(Hit::new(ray, dist, normal, normal, Vector2::new(u, v), 0))
+    }
+}
+
+impl Bounded for Triangle {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[self.a, self.b, self.c]))
+    }
+}
+
+impl Drawable for Triangle {}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use approx;
+    use crate::tracer::colour::Colour;
+    use crate::tracer::geom::triangle::Triangle;
+    use crate::tracer::geom::Intersects;
+    use crate::tracer::Ray;
+
+
+    #[test]
+    fn test_intersects() {
+        let triangle = Triangle::new(Vector3::new(-1.0, -1.0, 3.0), Vector3::new(1.0, -1.0, 3.0),
+                                     Vector3::new(0.0, 1.0, 3.0), Colour::black(), 0.6, 0.4, 2.0);
+        let ray = Ray{
+            orig: Vector3::new(0.0,0.0,0.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        let hit = triangle.intersect(&ray, 0.0001, f64::INFINITY).unwrap();
+        approx::assert_ulps_eq!(hit.dist, 3.0, max_ulps =  3);
+        approx::assert_ulps_eq!(0.0, hit.normal[0],max_ulps = 3);
+        approx::assert_ulps_eq!(0.0, hit.normal[1],max_ulps = 3);
+        approx::assert_ulps_eq!(1.0, hit.normal[2],max_ulps = 3);
+        assert!(!hit.front_face);
+        approx::assert_ulps_eq!(-1.0, hit.facing_normal()[2],max_ulps = 3);
+        approx::assert_ulps_eq!(0.25, hit.uv[0],max_ulps = 3);
+        approx::assert_ulps_eq!(0.5, hit.uv[1],max_ulps = 3);
+
+        let miss = Ray{
+            orig: Vector3::new(0.9,0.9,0.0),
+            dir: Vector3::new(0.0, 0.0, 1.0)
+        };
+        assert!(triangle.intersect(&miss, 0.0001, f64::INFINITY).is_none());
+    }
+
+}
@@ -0,0 +1,130 @@
+use rand::RngCore;
+
+use crate::tracer::{Ray, SceneState};
+use crate::tracer::aov::AovSample;
+use crate::tracer::colour::Colour;
+use crate::tracer::debug::{Depth, Heatmap, HeatmapCount, Normals, Uv};
+use crate::tracer::occlusion::Clay;
+use crate::tracer::path::PathTracer;
+use crate::tracer::phong::Phong;
+
+/// Works out the light arriving along a camera ray. The scene handles finding what rays hit,
+/// and rendering handles camera sampling and averaging pixels, so an integrator is only the
+/// shading model in between.
+pub trait Integrator: Send + Sync {
+    /// What scene files and the command line call this integrator.
+    fn name(&self) -> &str;
+
+    /// Radiance arriving back along `ray`. Camera rays pass an `AovSample` to be filled in with
+    /// what they hit, integrators that don't fill it in leave the defaults.
+    fn radiance(&self, scene: &SceneState, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour;
+}
+
+/// Makes a fresh integrator, registered under a name.
+pub type IntegratorFactory = Box<dyn Fn() -> Box<dyn Integrator> + Send + Sync>;
+
+/// The integrators that can be picked by name. The default registry has the built in ones, and
+/// other crates can register their own before loading a scene.
+pub struct IntegratorRegistry {
+    factories: Vec<(String, IntegratorFactory)>,
+}
+
+impl IntegratorRegistry {
+    /// A registry with no integrators at all.
+    pub fn empty() -> IntegratorRegistry {
+        IntegratorRegistry { factories: vec![] }
+    }
+
+    /// Adds an integrator, replacing any already registered under the same name.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+        where F: Fn() -> Box<dyn Integrator> + Send + Sync + 'static {
+        let factory: IntegratorFactory = Box::new(factory);
+        match self.factories.iter_mut().find(|(known, _)| known == name) {
+            Some(entry) => entry.1 = factory,
+            None => self.factories.push((name.to_string(), factory))
+        }
+    }
+
+    pub fn create(&self, name: &str) -> Result<Box<dyn Integrator>, String> {
+        self.factories.iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, factory)| factory())
+            .ok_or_else(|| format!("unknown integrator '{}', expected one of {}", name, self.names().join(", ")))
+    }
+
+    /// Registered names, in the order they were first registered.
+    pub fn names(&self) -> Vec<&str> {
+        self.factories.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+impl Default for IntegratorRegistry {
+    /// Phong shading, also what scenes use when they don't pick, path tracing, a clay render,
+    /// and the debug views.
+    fn default() -> IntegratorRegistry {
+        let mut registry = IntegratorRegistry::empty();
+        registry.register("phong", || Box::new(Phong));
+        registry.register("path", || Box::new(PathTracer));
+        registry.register("ao", || Box::new(Clay));
+        registry.register("normals", || Box::new(Normals));
+        registry.register("depth", || Box::new(Depth));
+        registry.register("uv", || Box::new(Uv));
+        registry.register("heatmap", || Box::new(Heatmap { count: HeatmapCount::IntersectionTests }));
+        registry.register("bvh-heatmap", || Box::new(Heatmap { count: HeatmapCount::BvhNodes }));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::RngCore;
+
+    use crate::Resolution;
+    use crate::tracer::{Camera, Ray, SceneState};
+    use crate::tracer::aov::AovSample;
+    use crate::tracer::colour::Colour;
+    use crate::tracer::integrator::{Integrator, IntegratorRegistry};
+
+    //Shades by which way the ray is going, without looking at the scene at all
+    struct Direction;
+
+    impl Integrator for Direction {
+        fn name(&self) -> &str {
+            "direction"
+        }
+
+        fn radiance(&self, _scene: &SceneState, ray: &Ray, _aov: Option<&mut AovSample>, _rng: &mut dyn RngCore) -> Colour {
+            let dir = ray.dir();
+            Colour::new(dir[0].abs(), dir[1].abs(), dir[2].abs())
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = IntegratorRegistry::default();
+        assert_eq!(registry.names(), vec!["phong", "path", "ao", "normals", "depth", "uv", "heatmap", "bvh-heatmap"]);
+        for name in registry.names() {
+            assert_eq!(registry.create(name).unwrap().name(), name);
+        }
+        assert!(registry.create("direction").err().unwrap().contains("phong, path"));
+
+        registry.register("direction", || Box::new(Direction));
+        registry.register("path", || Box::new(Direction));
+        assert_eq!(registry.names().last(), Some(&"direction"));
+        assert_eq!(registry.create("path").unwrap().name(), "direction");
+    }
+
+    #[test]
+    fn test_custom_integrator_renders() {
+        let camera = Camera::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        let mut scene = SceneState::new(vec![], vec![], camera, 0.1, Colour::black());
+        scene.integrator = Box::new(Direction);
+        let mut frame = vec![0; 3 * 3 * 4];
+        scene.rasterise(&mut frame, &Resolution { width: 3, height: 3 }, 1, 1);
+        //the middle pixel looks mostly straight ahead, the corners further off to the sides
+        let (middle, corner) = (&frame[16..20], &frame[0..4]);
+        assert!(middle[2] > middle[0] && middle[2] > middle[1]);
+        assert!(corner[0] > middle[0] && corner[2] < middle[2]);
+    }
+}
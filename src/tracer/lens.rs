@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+use crate::tracer::projection::View;
+
+//Width of a full frame 35mm sensor in metres, used to turn an f-stop into an aperture
+const SENSOR_WIDTH: f64 = 0.036;
+
+/// Outline of the lens opening, which gives out of focus highlights their shape.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Aperture {
+    Disk,
+    /// A regular polygon with this many blades, at least 3.
+    Polygon(u32),
+}
+
+impl Aperture {
+    /// Maps a point in the unit square onto the aperture, scaled to fit inside the unit circle.
+    /// Points spread evenly over the square come out spread evenly over the aperture.
+    pub fn sample(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        match *self {
+            Aperture::Disk => {
+                let radius = u.sqrt();
+                let angle = 2.0 * PI * v;
+                (radius * angle.cos(), radius * angle.sin())
+            }
+            Aperture::Polygon(blades) => {
+                //u picks one of the triangles fanning out from the centre, what's left of it and v
+                //a point within that triangle
+                let scaled = u * blades as f64;
+                let blade = (scaled as u32).min(blades - 1);
+                let step = 2.0 * PI / blades as f64;
+                let (a, b) = ((blade as f64 * step).sin_cos(), ((blade + 1) as f64 * step).sin_cos());
+                let (mut u, mut v) = (scaled - blade as f64, v);
+                if u + v > 1.0 {
+                    u = 1.0 - u;
+                    v = 1.0 - v;
+                }
+                (u * a.1 + v * b.1, u * a.0 + v * b.0)
+            }
+        }
+    }
+}
+
+/// A thin lens in front of the camera, everything at `focus_dist` is sharp and the rest blurs
+/// more the wider the aperture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ThinLens {
+    pub radius: f64,
+    /// Measured along the view direction from the camera's position.
+    pub focus_dist: f64,
+    pub aperture: Aperture,
+}
+
+impl ThinLens {
+    /// The lens radius a camera would have at `f_stop`, assuming scene units are metres and a
+    /// full frame sensor behind a lens giving `fov` degrees across.
+    pub fn radius_for_f_stop(f_stop: f64, fov: f64) -> f64 {
+        let focal_length = SENSOR_WIDTH / 2.0 / (fov.to_radians() / 2.0).tan();
+        focal_length / (2.0 * f_stop)
+    }
+
+    /// Moves a pinhole ray to start from the point on the lens `lens_sample` maps to, still
+    /// passing through where the pinhole ray meets the plane `focus_dist` along the view.
+    pub fn ray_through(&self, lens_sample: (f64, f64), view: &View, orig: Vector3<f64>, dir: Vector3<f64>)
+                       -> (Vector3<f64>, Vector3<f64>) {
+        let focus_point = orig + dir * (self.focus_dist / dir.dot(&view.forward));
+        let (x, y) = self.aperture.sample(lens_sample);
+        let lens_orig = orig + (view.right * x + view.up * y) * self.radius;
+        (lens_orig, focus_point - lens_orig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use crate::tracer::Camera;
+    use crate::tracer::lens::{Aperture, ThinLens};
+    use crate::tracer::projection::View;
+
+    #[test]
+    fn test_aperture_samples_stay_inside() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..1000 {
+            let (x, y) = Aperture::Disk.sample((rng.gen(), rng.gen()));
+            assert!(x * x + y * y <= 1.0);
+            //a hexagon's edges are sqrt(3)/2 from the centre, half way between the corners
+            let (x, y) = Aperture::Polygon(6).sample((rng.gen(), rng.gen()));
+            let angle = y.atan2(x).rem_euclid(std::f64::consts::PI / 3.0) - std::f64::consts::PI / 6.0;
+            assert!((x * x + y * y).sqrt() * angle.cos() <= 3.0_f64.sqrt() / 2.0 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_rays_meet_at_focus() {
+        let lens = ThinLens { radius: 0.5, focus_dist: 4.0, aperture: Aperture::Disk };
+        let mut rng = StdRng::seed_from_u64(5);
+        let view = View::new(&Camera::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 1.0), 90.0), 10.0, 10.0);
+        for _ in 0..10 {
+            let (orig, dir) = lens.ray_through((rng.gen(), rng.gen()), &view, Vector3::zeros(), Vector3::new(0.25, 0.0, 1.0));
+            assert_eq!(orig[2], 0.0);
+            let at_focus = orig + dir * (4.0 / dir[2]);
+            approx::assert_abs_diff_eq!(at_focus, Vector3::new(1.0, 0.0, 4.0), epsilon = 1e-12);
+        }
+        approx::assert_ulps_eq!(ThinLens::radius_for_f_stop(2.0, 90.0), 0.0045);
+    }
+}
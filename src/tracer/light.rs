@@ -0,0 +1,355 @@
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+use rand::prelude::*;
+
+use crate::tracer::Ray;
+use crate::tracer::colour::Colour;
+
+/// Light arriving at a point from one light, before shadowing and the surface are taken into
+/// account.
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+    /// Towards the light, not necessarily unit length.
+    pub to_light: Vector3<f64>,
+    /// How far a shadow ray has to get without hitting anything, infinite for distant lights.
+    pub dist: f64,
+    pub colour: Colour,
+    pub intensity: f64,
+}
+
+pub trait Light: Send + Sync {
+    /// How the light reaches `point`, `None` if it can't. Lights with area pick a random point
+    /// on their surface each time.
+    fn sample(&self, point: &Vector3<f64>, rng: &mut dyn RngCore) -> Option<LightSample>;
+
+    /// Shadow rays averaged at each shading point.
+    fn samples(&self) -> u32 {
+        1
+    }
+
+    /// Where a ray sees the light's own surface, for lights that have one.
+    fn intersect(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> Option<f64> {
+        None
+    }
+
+    /// What a ray hitting the light's surface sees.
+    fn emitted(&self) -> Colour {
+        Colour::black()
+    }
+}
+
+pub struct PointLight {
+    pub pos: Vector3<f64>,
+    pub colour: Colour,
+    pub intensity:f64
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vector3<f64>, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_light = self.pos - point;
+        Some(LightSample {
+            to_light,
+            dist: to_light.norm(),
+            colour: self.colour,
+            intensity: self.intensity,
+        })
+    }
+}
+
+/// A light so far away, like the sun, that it shines the same way everywhere and its shadows are
+/// parallel.
+pub struct DirectionalLight {
+    /// The way the light travels, from the light towards the scene.
+    pub dir: Vector3<f64>,
+    pub colour: Colour,
+    pub intensity: f64,
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vector3<f64>, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        Some(LightSample {
+            to_light: -self.dir,
+            dist: f64::INFINITY,
+            colour: self.colour,
+            intensity: self.intensity,
+        })
+    }
+}
+
+/// A point light shining in a cone, full strength inside `inner` degrees of `dir` and fading
+/// smoothly to nothing at `outer`.
+pub struct SpotLight {
+    pub pos: Vector3<f64>,
+    /// Along the middle of the cone, unit length.
+    pub dir: Vector3<f64>,
+    pub inner: f64,
+    pub outer: f64,
+    pub colour: Colour,
+    pub intensity: f64,
+}
+
+impl SpotLight {
+    /// How much of the light leaves in `dir`, which is unit length.
+    pub fn falloff(&self, dir: &Vector3<f64>) -> f64 {
+        let cos_angle = self.dir.dot(dir);
+        let (cos_inner, cos_outer) = (self.inner.to_radians().cos(), self.outer.to_radians().cos());
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vector3<f64>, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_light = self.pos - point;
+        let dist = to_light.norm();
+        if dist == 0.0 {
+            return None;
+        }
+        let falloff = self.falloff(&(-to_light / dist));
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            to_light,
+            dist,
+            colour: self.colour,
+            intensity: self.intensity * falloff,
+        })
+    }
+}
+
+/// The surface an area light shines from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AreaShape {
+    /// Spanned by the edges `u` and `v` either side of `centre`, which are at right angles, and
+    /// shining towards `u` cross `v`.
+    Rectangle { centre: Vector3<f64>, u: Vector3<f64>, v: Vector3<f64> },
+    /// Shining towards `normal`, which is unit length.
+    Disk { centre: Vector3<f64>, normal: Vector3<f64>, radius: f64 },
+    /// Shining outwards in every direction.
+    Sphere { centre: Vector3<f64>, radius: f64 },
+}
+
+/// A light spread across a surface, so shadows soften the further they fall from whatever casts
+/// them. Like a point light it doesn't fall off with distance, each shadow ray carries an even
+/// share of `intensity`. Rectangles and disks are only seen and only shine on their front.
+pub struct AreaLight {
+    pub shape: AreaShape,
+    pub colour: Colour,
+    pub intensity: f64,
+    pub samples: u32,
+}
+
+impl AreaLight {
+    /// A random point on the part of the surface `point` can see, `None` if it's behind the
+    /// light.
+    fn point_on_surface(&self, point: &Vector3<f64>, rng: &mut dyn RngCore) -> Option<Vector3<f64>> {
+        match self.shape {
+            AreaShape::Rectangle { centre, u, v } => {
+                if (point - centre).dot(&u.cross(&v)) <= 0.0 {
+                    return None;
+                }
+                Some(centre + u * (rng.gen::<f64>() - 0.5) + v * (rng.gen::<f64>() - 0.5))
+            }
+            AreaShape::Disk { centre, normal, radius } => {
+                if (point - centre).dot(&normal) <= 0.0 {
+                    return None;
+                }
+                let (across, up) = perpendiculars(&normal);
+                let dist = radius * rng.gen::<f64>().sqrt();
+                let (sin, cos) = (2.0 * PI * rng.gen::<f64>()).sin_cos();
+                Some(centre + (across * cos + up * sin) * dist)
+            }
+            AreaShape::Sphere { centre, radius } => {
+                //directions spread evenly over the cone the sphere fills as seen from the point
+                let to_centre = centre - point;
+                let dist = to_centre.norm();
+                if dist <= radius {
+                    return None;
+                }
+                let axis = to_centre / dist;
+                let cos_max = (1.0 - (radius / dist).powi(2)).sqrt();
+                let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_max);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let (sin_phi, cos_phi) = (2.0 * PI * rng.gen::<f64>()).sin_cos();
+                let (across, up) = perpendiculars(&axis);
+                let dir = axis * cos_theta + (across * cos_phi + up * sin_phi) * sin_theta;
+                let near = dist * cos_theta - (radius * radius - dist * dist * sin_theta * sin_theta).max(0.0).sqrt();
+                Some(point + dir * near)
+            }
+        }
+    }
+}
+
+/// Two unit vectors at right angles to each other and to `normal`.
+pub(crate) fn perpendiculars(normal: &Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let other = if normal[0].abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let across = normal.cross(&other).normalize();
+    (across, normal.cross(&across))
+}
+
+impl Light for AreaLight {
+    fn sample(&self, point: &Vector3<f64>, rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_light = self.point_on_surface(point, rng)? - point;
+        let dist = to_light.norm();
+        if dist == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            to_light,
+            dist,
+            colour: self.colour,
+            intensity: self.intensity,
+        })
+    }
+
+    fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let dist = match self.shape {
+            AreaShape::Rectangle { centre, u, v } => {
+                let normal = u.cross(&v);
+                if ray.dir.dot(&normal) >= 0.0 {
+                    return None;
+                }
+                let dist = (centre - ray.orig).dot(&normal) / ray.dir.dot(&normal);
+                let offset = ray.point_along(dist) - centre;
+                if offset.dot(&u).abs() > u.norm_squared() / 2.0 || offset.dot(&v).abs() > v.norm_squared() / 2.0 {
+                    return None;
+                }
+                dist
+            }
+            AreaShape::Disk { centre, normal, radius } => {
+                if ray.dir.dot(&normal) >= 0.0 {
+                    return None;
+                }
+                let dist = (centre - ray.orig).dot(&normal) / ray.dir.dot(&normal);
+                if (ray.point_along(dist) - centre).norm() > radius {
+                    return None;
+                }
+                dist
+            }
+            AreaShape::Sphere { centre, radius } => {
+                let to_orig = ray.orig - centre;
+                let b = to_orig.dot(&ray.dir);
+                let discriminant = b * b - (to_orig.dot(&to_orig) - radius * radius);
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let near = -b - discriminant.sqrt();
+                if near >= t_min { near } else { -b + discriminant.sqrt() }
+            }
+        };
+        if dist.is_finite() && (t_min..=t_max).contains(&dist) {
+            Some(dist)
+        } else {
+            None
+        }
+    }
+
+    fn emitted(&self) -> Colour {
+        self.colour * self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::tracer::Ray;
+    use crate::tracer::colour::Colour;
+    use crate::tracer::light::{AreaLight, AreaShape, DirectionalLight, Light, PointLight, SpotLight};
+
+    #[test]
+    fn test_samples() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let point = PointLight { pos: Vector3::new(0.0, 4.0, 0.0), colour: Colour::white(), intensity: 0.5 };
+        let sample = point.sample(&Vector3::new(0.0, 1.0, 0.0), &mut rng).unwrap();
+        assert_eq!(sample.to_light, Vector3::new(0.0, 3.0, 0.0));
+        assert_eq!(sample.dist, 3.0);
+
+        let sun = DirectionalLight { dir: Vector3::new(0.0, -1.0, 1.0), colour: Colour::white(), intensity: 1.0 };
+        for point in &[Vector3::zeros(), Vector3::new(100.0, -3.0, 7.0)] {
+            let sample = sun.sample(point, &mut rng).unwrap();
+            assert_eq!(sample.to_light, Vector3::new(0.0, 1.0, -1.0));
+            assert_eq!(sample.dist, f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn test_spot_cone() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let spot = SpotLight {
+            pos: Vector3::new(0.0, 2.0, 0.0),
+            dir: Vector3::new(0.0, -1.0, 0.0),
+            inner: 20.0,
+            outer: 40.0,
+            colour: Colour::white(),
+            intensity: 2.0,
+        };
+        assert_eq!(spot.sample(&Vector3::zeros(), &mut rng).unwrap().intensity, 2.0);
+        //30 degrees off the axis is part way through the fade, 45 is outside the cone
+        let part = spot.sample(&Vector3::new(2.0 * 30.0_f64.to_radians().tan(), 0.0, 0.0), &mut rng).unwrap();
+        assert!(part.intensity > 0.0 && part.intensity < 2.0);
+        assert!(spot.sample(&Vector3::new(2.0, 0.0, 0.0), &mut rng).is_none());
+        assert!(spot.sample(&Vector3::new(0.0, 3.0, 0.0), &mut rng).is_none());
+
+        let mut last = 1.0;
+        for degrees in 20..=40 {
+            let angle = (degrees as f64).to_radians();
+            let falloff = spot.falloff(&Vector3::new(angle.sin(), -angle.cos(), 0.0));
+            assert!(falloff <= last);
+            last = falloff;
+        }
+        assert_eq!(last, 0.0);
+    }
+
+    #[test]
+    fn test_area_samples_land_on_the_surface() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let centre = Vector3::new(0.0, 4.0, 0.0);
+        let shapes = [
+            AreaShape::Rectangle { centre, u: Vector3::new(2.0, 0.0, 0.0), v: Vector3::new(0.0, 0.0, 1.0) },
+            AreaShape::Disk { centre, normal: Vector3::new(0.0, -1.0, 0.0), radius: 1.5 },
+            AreaShape::Sphere { centre, radius: 1.0 },
+        ];
+        for shape in shapes.iter() {
+            let light = AreaLight { shape: *shape, colour: Colour::white(), intensity: 1.0, samples: 4 };
+            for _ in 0..100 {
+                let sample = light.sample(&Vector3::zeros(), &mut rng).unwrap();
+                let on_light = sample.to_light;
+                approx::assert_abs_diff_eq!(sample.dist, on_light.norm());
+                match shape {
+                    AreaShape::Rectangle { .. } => assert!(on_light[0].abs() <= 1.0 && on_light[2].abs() <= 0.5 && on_light[1] == 4.0),
+                    AreaShape::Disk { .. } => assert!(on_light.xz().norm() <= 1.5 && on_light[1] == 4.0),
+                    //and on the half of the sphere facing the point
+                    AreaShape::Sphere { .. } => {
+                        approx::assert_abs_diff_eq!((on_light - centre).norm(), 1.0, epsilon = 1e-9);
+                        assert!(on_light[1] <= 4.0);
+                    }
+                }
+                //a camera ray towards the sample sees the light there
+                let dist = light.intersect(&Ray::new(Vector3::zeros(), on_light), 0.0, f64::INFINITY).unwrap();
+                approx::assert_abs_diff_eq!(dist, sample.dist, epsilon = 1e-9);
+            }
+        }
+
+        //the rectangle and disk only shine downwards
+        let above = Vector3::new(0.0, 6.0, 0.0);
+        let rect = AreaLight { shape: shapes[0], colour: Colour::white(), intensity: 1.0, samples: 4 };
+        let disk = AreaLight { shape: shapes[1], colour: Colour::white(), intensity: 1.0, samples: 4 };
+        assert!(rect.sample(&above, &mut rng).is_none());
+        assert!(disk.sample(&above, &mut rng).is_none());
+    }
+}
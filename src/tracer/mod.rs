@@ -1,19 +1,101 @@
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3};
 
-use colour::RGB;
-use geom::Drawable;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use colour::Colour;
+use colour::tonemap::DisplayTransform;
+use geom::{Drawable, Hit};
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
 use crate::Resolution;
+use crate::buffer::FrameBuffer;
+use crate::tracer::aov::{Aov, AovSample};
 use crate::tracer::colour::Material;
+use crate::tracer::geom::aabb::Aabb;
+use crate::tracer::geom::bvh::Bvh;
+use crate::tracer::integrator::Integrator;
+use crate::tracer::lens::ThinLens;
+use crate::tracer::light::Light;
+use crate::tracer::occlusion::AmbientOcclusion;
+use crate::tracer::phong::Phong;
+use crate::tracer::projection::{Perspective, Projection, View};
+use crate::tracer::sampler::{Sampler, SamplerType};
+use crate::tracer::tile::Tile;
 
 pub mod geom;
 pub mod colour;
+pub mod tile;
+pub mod aov;
+pub mod lens;
+pub mod projection;
+pub mod light;
+pub mod integrator;
+pub mod phong;
+pub mod path;
+pub mod debug;
+pub mod occlusion;
+pub mod sampler;
+
+//How often a progressive render shows its finished tiles
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+//Bounces a ray can take off mirrors and through glass unless the scene says otherwise
+pub const DEFAULT_MAX_DEPTH: u32 = 5;
+//Hits nearer than this are ignored so rays leaving a surface don't find it again
+const RAY_EPSILON: f64 = 0.00005;
+//Up vectors closer than this to the view direction leave the camera's right undefined
+const PARALLEL_UP_EPSILON: f64 = 1e-9;
 
 pub struct Camera {
     pub pos: Vector3<f64>,
     pub dir: Vector3<f64>,
     pub fov: f64,
+    /// Which way is up in the world, only needs to be roughly perpendicular to `dir`.
+    pub up: Vector3<f64>,
+    /// Degrees to turn the camera about `dir`, positive rolls the top of the image to the right.
+    pub roll: f64,
+    /// Depth of field, `None` for a pinhole camera where everything is sharp.
+    pub lens: Option<ThinLens>,
+    pub projection: Box<dyn Projection>,
+}
+
+impl Camera {
+    /// A camera with the world's y axis as up and no roll.
+    pub fn new(pos: Vector3<f64>, dir: Vector3<f64>, fov: f64) -> Camera {
+        Camera {
+            pos,
+            dir,
+            fov,
+            up: Vector3::new(0.0, 1.0, 0.0),
+            roll: 0.0,
+            lens: None,
+            projection: Box::new(Perspective),
+        }
+    }
+
+    /// A camera at `pos` pointing at `target`.
+    pub fn look_at(pos: Vector3<f64>, target: Vector3<f64>, up: Vector3<f64>, fov: f64) -> Camera {
+        Camera {
+            up,
+            ..Camera::new(pos, target - pos, fov)
+        }
+    }
+
+    /// Why the camera can't be used to build a view, if it can't.
+    pub fn degenerate(&self) -> Option<&'static str> {
+        if self.dir.norm() == 0.0 {
+            Some("direction can't be zero length")
+        } else if self.up.norm() == 0.0 {
+            Some("up can't be zero length")
+        } else if self.up.normalize().cross(&self.dir.normalize()).norm() < PARALLEL_UP_EPSILON {
+            Some("up can't be parallel to the view direction")
+        } else {
+            None
+        }
+    }
 }
 
 pub struct Ray {
@@ -24,204 +106,414 @@ pub struct Ray {
 impl Ray {
     pub fn new(orig: Vector3<f64>, dir: Vector3<f64>) -> Ray {
         Ray {
-            orig: orig,
+            orig,
             dir: dir.normalize(),
         }
     }
 
+    pub fn orig(&self) -> &Vector3<f64> {
+        &self.orig
+    }
+
+    /// Always unit length.
+    pub fn dir(&self) -> &Vector3<f64> {
+        &self.dir
+    }
+
     pub fn point_along(&self, dist: f64) -> Vector3<f64> {
         (self.dir * dist) + self.orig
     }
 }
 
-pub struct PointLight {
-    pub pos: Vector3<f64>,
-    pub colour: RGB,
-    pub intensity:f64
-}
-
 pub struct SceneState {
-    pub geom: Vec<Box<dyn Drawable>>,
-    pub point_lights: Vec<PointLight>,
+    geom: Vec<Box<dyn Drawable>>,
+    pub lights: Vec<Box<dyn Light>>,
     pub camera: Camera,
     pub ambient: f64,
-    pub background_colour:RGB
+    pub background_colour:Colour,
+    /// How many reflections or refractions deep a ray is followed before only local lighting counts,
+    /// or with path tracing how many bounces of any kind.
+    pub max_depth: u32,
+    /// How finished pixels are tone mapped and encoded for the frame buffer.
+    pub display: DisplayTransform,
+    /// Shading model turning the rays rendering sends out into colours.
+    pub integrator: Box<dyn Integrator>,
+    /// Darkens the ambient term by how occluded each point is, when set. Also the settings the
+    /// clay render uses.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    /// Where in each pixel the camera rays go.
+    pub sampler: Box<dyn Sampler>,
+    /// Renders with the same seed come out the same, whatever the number of threads.
+    pub seed: u64,
+    //Indices into geom of the objects in the BVH, and of the unbounded ones tested on every ray
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+    bvh: Bvh,
+    //For the material id AOV, objects with equal materials share an id
+    material_ids: Vec<usize>,
 }
 
-struct HitInformation<'a> {
-    dist: f64,
-    material: &'a Material,
-    point: Vector3<f64>,
-    normal: Vector3<f64>,
+//A tile's pixels, and its pixels for each AOV being rendered
+struct RenderedTile {
+    radiance: Vec<Colour>,
+    aovs: Vec<Vec<Colour>>,
 }
 
-impl SceneState {
-    pub fn rasterise(&self, frame: &mut Vec<u8>, res: &Resolution, samples:u8) {
-        let width = res.width;
-        let height = res.height;
-        let (top_left, x_stride, y_stride) = screen_to_coord_stride(width as f64, height as f64, &self.camera);
-        //find better seed for this later
-        let mut rng = rand::thread_rng();
-
-        for y in 0..height {
-            let y_pixel_pos = &top_left + (&y_stride * (y as f64));
-            for x in 0..width {
-                // let pixel_pos = screen_to_coord(x, y, width as f64, height as f64, &self.camera);
-                let mut r_total:u32 = 0;
-                let mut g_total:u32 = 0;
-                let mut b_total:u32 = 0;
-
-                for sample in 0..samples {
-                    let rand_x:f64 = rng.gen();
-                    let rand_y:f64 = rng.gen();
-
-                    let pixel_pos = if sample == 0 {
-                        y_pixel_pos + (&x_stride * (x as f64))
-                    } else {
-                        y_pixel_pos + (&x_stride * (x as f64)) + (&x_stride * rand_x) + (&y_stride * rand_y)
-                    };
+/// Where a ray met the nearest object, and what it's made of there.
+pub struct HitInformation<'a> {
+    /// Index into the scene's `geom`.
+    pub object: usize,
+    pub material: &'a Material,
+    pub dist: f64,
+    pub point: Vector3<f64>,
+    /// Faces back along the ray, `front_face` says whether that is the outside of the surface.
+    pub normal: Vector3<f64>,
+    pub front_face: bool,
+    /// Texture coordinates, barycentric on triangles without any.
+    pub uv: Vector2<f64>,
+}
 
+/// How much work finding a ray's closest hit took.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TraversalStats {
+    /// BVH nodes popped during traversal, whether or not the ray entered their box.
+    pub nodes_visited: usize,
+    /// Objects tested for a hit, unbounded ones included. Triangles tested inside a mesh count
+    /// as one test of the mesh.
+    pub intersection_tests: usize,
+}
 
-                    let ray = Ray::new(
-                        self.camera.pos,
-                        pixel_pos - &self.camera.pos,
-                    );
-                    let draw_colour = self.cast_ray(ray);
-                    r_total += draw_colour.r as u32;
-                    g_total += draw_colour.g as u32;
-                    b_total += draw_colour.b as u32;
+impl SceneState {
+    pub fn new(geom: Vec<Box<dyn Drawable>>, lights: Vec<Box<dyn Light>>, camera: Camera, ambient: f64,
+               background_colour: Colour) -> SceneState {
+        let mut bounded = vec![];
+        let mut bounds = vec![];
+        let mut unbounded = vec![];
+        for (index, object) in geom.iter().enumerate() {
+            match object.bounds() {
+                Some(object_bounds) => {
+                    bounded.push(index);
+                    bounds.push(object_bounds);
                 }
-                let array_loc: usize = ((x as u32 + (y as u32 * width as u32)) * 4) as usize;
-                //Need a better memcopy though kind of unsafe
-                frame[array_loc] = (r_total / samples as u32) as u8;
-                frame[array_loc + 1] = (g_total / samples as u32) as u8;
-                frame[array_loc + 2] = (b_total / samples as u32) as u8;
-                frame[array_loc + 3] = 255;
+                None => unbounded.push(index)
             }
         }
+        //every object has a single material so far, so where it's looked up doesn't matter
+        let mut materials: Vec<&Material> = vec![];
+        let mut material_ids = vec![];
+        for object in &geom {
+            let material = object.material_at(&Vector3::zeros());
+            let id = match materials.iter().position(|&known| known == material) {
+                Some(id) => id,
+                None => {
+                    materials.push(material);
+                    materials.len() - 1
+                }
+            };
+            material_ids.push(id);
+        }
+        SceneState {
+            geom,
+            lights,
+            camera,
+            ambient,
+            background_colour,
+            max_depth: DEFAULT_MAX_DEPTH,
+            display: DisplayTransform::default(),
+            integrator: Box::new(Phong),
+            ambient_occlusion: None,
+            sampler: SamplerType::default().sampler(),
+            seed: 0,
+            bounded,
+            unbounded,
+            bvh: Bvh::build(&bounds),
+            material_ids,
+        }
     }
 
+    pub fn geom(&self) -> &[Box<dyn Drawable>] {
+        &self.geom
+    }
 
-    fn cast_ray(&self, ray:Ray) -> RGB {
-        let mut hit_info: Option<HitInformation> = None;
-        for object in &self.geom {
-            match object.intersect(&ray) {
-                (hit, _) if hit < 0.0 => {}
-                (dist, Some(normal)) => {
-                    match hit_info {
+    /// Size of the hierarchy bounded objects are found through.
+    pub fn bvh_node_count(&self) -> usize {
+        self.bvh.node_count()
+    }
+
+    /// Id shared by every object in `geom` with the same material as `object`.
+    pub fn material_id(&self, object: usize) -> usize {
+        self.material_ids[object]
+    }
+
+    /// Bounds of every finite object in the scene, `None` if there aren't any. Unbounded objects
+    /// such as planes aren't included, `unbounded_count` says how many were left out.
+    pub fn extent(&self) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+
+    pub fn unbounded_count(&self) -> usize {
+        self.unbounded.len()
+    }
+
+    /// Renders the scene into an RGBA frame, splitting the image into tiles shared out across a
+    /// pool of `threads` workers. Each tile gets its own rng seeded from the scene's `seed` and
+    /// the tile, so the output doesn't depend on the number of threads or the order the tiles
+    /// finish in.
+    pub fn rasterise(&self, frame: &mut [u8], res: &Resolution, samples: u8, threads: u8) {
+        let frame = Mutex::new(frame);
+        self.render_tiles(res, samples, threads, &[], |tile, rendered| {
+            let pixels = self.display_pixels(&rendered.radiance);
+            let mut frame = frame.lock().unwrap();
+            tile::copy_to_frame(tile, &pixels, &mut frame, res, 4);
+        });
+    }
+
+    /// Renders the whole scene into a `FrameBuffer`, the display pixels, the radiance behind them
+    /// and any AOVs the buffer has room for, and publishes it once finished.
+    pub fn render(&self, frame: &FrameBuffer, samples: u8, threads: u8) {
+        self.render_tiles(&frame.resolution(), samples, threads, frame.aovs(), |tile, rendered| {
+            frame.write_tile(tile, &self.display_pixels(&rendered.radiance), &rendered.radiance, &rendered.aovs);
+        });
+        frame.publish();
+    }
+
+    /// Renders the scene straight into a `FrameBuffer`, publishing finished tiles as they come in
+    /// so a window reading the front buffer can show the image while it is still rendering.
+    pub fn render_progressive(&self, frame: &FrameBuffer, samples: u8, threads: u8) {
+        let last_publish = Mutex::new(Instant::now());
+        self.render_tiles(&frame.resolution(), samples, threads, frame.aovs(), |tile, rendered| {
+            frame.write_tile(tile, &self.display_pixels(&rendered.radiance), &rendered.radiance, &rendered.aovs);
+            let mut last_publish = last_publish.lock().unwrap();
+            if last_publish.elapsed() >= PUBLISH_INTERVAL {
+                frame.publish();
+                *last_publish = Instant::now();
+            }
+        });
+        frame.publish();
+    }
+
+    fn render_tiles<F>(&self, res: &Resolution, samples: u8, threads: u8, aovs: &[Aov], on_tile: F)
+        where F: Fn(&Tile, &RenderedTile) + Sync {
+        let tiles = tile::tiles_for(res);
+        let next_tile = AtomicUsize::new(0);
+        let view = View::new(&self.camera, res.width as f64, res.height as f64);
+
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| {
+                    loop {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let tile = match tiles.get(index) {
+                            Some(tile) => tile,
+                            None => break
+                        };
+                        let mut rng = StdRng::seed_from_u64(tile_seed(self.seed, index));
+                        let rendered = self.render_tile(tile, &view, samples, aovs, &mut rng);
+                        on_tile(tile, &rendered);
+                    }
+                });
+            }
+        });
+    }
+
+    fn render_tile(&self, tile: &Tile, view: &View, samples: u8, aovs: &[Aov], rng: &mut StdRng) -> RenderedTile {
+        let mut radiance = Vec::with_capacity(tile.pixel_count());
+        let mut aov_pixels = vec![Vec::with_capacity(tile.pixel_count()); aovs.len()];
+
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                let mut total = Colour::black();
+                let mut aov_totals = vec![Colour::black(); aovs.len()];
+
+                let jitters = self.sampler.pixel_samples(samples as u32, rng);
+                //only drawn with a lens, so pinhole renders don't use up any of the rng
+                let lens_samples = match self.camera.lens {
+                    Some(_) => self.sampler.lens_samples(samples as u32, rng),
+                    None => vec![(0.5, 0.5); samples as usize]
+                };
+                for (sample, (jitter, lens_sample)) in jitters.into_iter().zip(lens_samples).enumerate() {
+                    let ray = match self.primary_ray(view, x as f64, y as f64, jitter, lens_sample) {
+                        Some(ray) => ray,
                         None => {
-                            let hit_point = ray.point_along(dist);
-                            hit_info = Some(HitInformation {
-                                dist: dist,
-                                material: object.material_at(&hit_point),
-                                point: hit_point,
-                                normal: normal,
-                            });
+                            //outside the projection, black with nothing hit
+                            for (aov, aov_total) in aovs.iter().zip(aov_totals.iter_mut()) {
+                                if !aov.is_averaged() && sample == 0 {
+                                    *aov_total = AovSample::default().value(*aov);
+                                }
+                            }
+                            continue;
                         }
-                        Some(prev_hit_info) if dist < prev_hit_info.dist => {
-                            let hit_point = ray.point_along(dist);
-                            hit_info = Some(HitInformation {
-                                dist: dist,
-                                material: object.material_at(&hit_point),
-                                point: hit_point,
-                                normal: normal,
-                            });
+                    };
+                    if aovs.is_empty() {
+                        total += self.trace(&ray, None, rng);
+                        continue;
+                    }
+                    let mut aov_sample = AovSample::default();
+                    total += self.trace(&ray, Some(&mut aov_sample), rng);
+                    for (aov, aov_total) in aovs.iter().zip(aov_totals.iter_mut()) {
+                        if aov.is_averaged() {
+                            *aov_total += aov_sample.value(*aov);
+                        } else if sample == 0 {
+                            *aov_total = aov_sample.value(*aov);
                         }
-                        Some(_) => {}
                     }
                 }
-                (_, _) => {
-                    println!("Some weird error happened with object");
+                radiance.push(total / samples as f64);
+                for ((aov, aov_total), pixels) in aovs.iter().zip(aov_totals).zip(aov_pixels.iter_mut()) {
+                    pixels.push(if aov.is_averaged() { aov_total / samples as f64 } else { aov_total });
                 }
             }
         }
+        RenderedTile { radiance, aovs: aov_pixels }
+    }
 
-        //TODO this needs a tidy up, 2 matches like this. Blergh
-        let draw_colour = match hit_info {
-            Some(info) => { self.colour_for_hit(info, ray) }
-            None => None
-        };
-
-        let draw_colour = match draw_colour {
-            Some(actual_colour) => { actual_colour }
-            None => RGB {
-                r: self.background_colour.r,
-                g: self.background_colour.g,
-                b: self.background_colour.b
+    /// The camera ray for a sample, moved onto the lens at `lens_sample` for depth of field.
+    fn primary_ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64), lens_sample: (f64, f64)) -> Option<Ray> {
+        let (orig, dir) = self.camera.projection.ray(view, x, y, jitter)?;
+        Some(match &self.camera.lens {
+            Some(lens) => {
+                let (orig, dir) = lens.ray_through(lens_sample, view, orig, dir);
+                Ray::new(orig, dir)
             }
-        };
-        return draw_colour;
-    }
-
-    fn colour_for_hit(&self, hit_info: HitInformation, ray:Ray) -> Option<RGB> {
-        let ambient = self.ambient;
-        let ambient_colour:RGB = hit_info.material.rgb.multiply(ambient);
-        let mut colour_pts:Vec<RGB> = Vec::new();
-        let reflect =  ray.dir - 2.0 * ray.dir.dot(&hit_info.normal) * hit_info.normal;
-
-        for light in &self.point_lights {
-            let mut in_shadow  = false;
-            let hit_to_light: Vector3<f64> = light.pos - hit_info.point;
-            let light_dist = hit_to_light.norm();
-            let new_ray: Ray = Ray::new(Vector3::from_data(hit_info.point.data), hit_to_light);
-            for object in &self.geom {
-                match object.intersect(&new_ray) {
-                    (dist, Some(_norm)) if dist > 0.00005 && dist < light_dist => {
-                        // println!("hit!");
-                        // let next_hit_point = new_ray.point_along(dist);
-                        in_shadow = true;
-                        break;
-                    }
-                    (_, _) => {}
+            None => Ray::new(orig, dir)
+        })
+    }
+
+    /// Tone maps and encodes a tile's radiance into RGBA bytes for display.
+    fn display_pixels(&self, radiance: &[Colour]) -> Vec<u8> {
+        radiance.iter()
+            .flat_map(|colour| {
+                let pixel = self.display.to_display(*colour);
+                [pixel.r, pixel.g, pixel.b, 255]
+            })
+            .collect()
+    }
+
+    /// Light arriving along a ray, worked out by the scene's integrator.
+    pub fn trace(&self, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+        self.integrator.radiance(self, ray, aov, rng)
+    }
+
+    /// The nearest light surface along the ray closer than `max_dist`, and how far away it is.
+    pub fn closest_emitter(&self, ray: &Ray, max_dist: f64) -> Option<(&dyn Light, f64)> {
+        let mut closest: Option<(&dyn Light, f64)> = None;
+        for light in &self.lights {
+            let t_max = closest.map_or(max_dist, |(_, dist)| dist);
+            if let Some(dist) = light.intersect(ray, RAY_EPSILON, t_max) {
+                if dist < t_max {
+                    closest = Some((light.as_ref(), dist));
                 }
             }
-            if !in_shadow {
-                let dot_n = hit_to_light.normalize().dot(&hit_info.normal);
-                let diff_frac = if dot_n > 0.0 {
-                    dot_n * hit_info.material.diffuse * light.intensity
-                } else {
-                  0.0
-                };
-                let light_reflect =  hit_to_light.normalize() - 2.0 * dot_n * &hit_info.normal;
-                let spec_frac:f64 = (light_reflect.dot(&ray.dir.normalize())).max(0.0).powf(hit_info.material.specular_exp) * hit_info.material.specular * light.intensity;
-                let spec_colour:RGB = hit_info.material.rgb.multiply(spec_frac);
-                // let diffuse_colour:RGB = RGB::new(0,0,0);
-                let diffuse_colour:RGB = hit_info.material.rgb.multiply(diff_frac);
-                colour_pts.push(RGB{
-                    r: std::cmp::min(spec_colour.r + diffuse_colour.r, 255),
-                    g: std::cmp::min(spec_colour.g + diffuse_colour.g, 255),
-                    b: std::cmp::min(spec_colour.b + diffuse_colour.b, 255)
-                });
-            }
-        }
-        let mut r:u32 = 0;
-        let mut g:u32 = 0;
-        let mut b:u32 = 0;
-        let size = colour_pts.len() as u8;
-        for colour in colour_pts {
-            r += colour.r as u32;
-            g += colour.g as u32;
-            b += colour.b as u32;
         }
-        if size > 0 {
-            r = r / size as u32;
-            g = g / size as u32;
-            b = b / size as u32;
+        closest
+    }
+
+    /// Finds the nearest object along the ray. Equally near hits go to whichever object comes
+    /// first in `geom`, so the result is the same as testing every object in order.
+    pub fn closest_hit(&self, ray: &Ray) -> Option<HitInformation<'_>> {
+        self.closest_hit_counted(ray).0
+    }
+
+    /// `closest_hit`, also counting the work it took to find.
+    pub fn closest_hit_counted(&self, ray: &Ray) -> (Option<HitInformation<'_>>, TraversalStats) {
+        let mut closest: Option<(Hit, usize)> = None;
+        let mut intersection_tests = 0;
+        let mut test = |index: usize, t_max: f64| -> Option<f64> {
+            intersection_tests += 1;
+            let hit = self.geom[index].intersect(ray, RAY_EPSILON, t_max)?;
+            match closest {
+                Some((closest_hit, closest_index))
+                    if closest_hit.dist < hit.dist || (closest_hit.dist == hit.dist && closest_index < index) => None,
+                _ => {
+                    closest = Some((hit, index));
+                    Some(hit.dist)
+                }
+            }
+        };
+        let nodes_visited = self.bvh.traverse(ray, f64::INFINITY, |item, t_max| test(self.bounded[item], t_max));
+        for &index in &self.unbounded {
+            test(index, f64::INFINITY);
         }
-        return Some(RGB {
-            r: std::cmp::min(r + ambient_colour.r as u32, 255) as u8,
-            g: std::cmp::min(g + ambient_colour.g as u32, 255) as u8,
-            b: std::cmp::min(b + ambient_colour.b as u32, 255) as u8
+
+        let hit_info = closest.map(|(hit, index)| {
+            HitInformation {
+                object: index,
+                material: self.geom[index].material_at(&hit.point),
+                dist: hit.dist,
+                point: hit.point,
+                normal: hit.facing_normal(),
+                front_face: hit.front_face,
+                uv: hit.uv,
+            }
         });
+        (hit_info, TraversalStats { nodes_visited, intersection_tests })
+    }
+
+    /// Whether anything sits along the ray before `max_dist`, for shadows.
+    pub fn occluded(&self, ray: &Ray, max_dist: f64) -> bool {
+        let blocks = |index: usize| self.geom[index].intersect(ray, RAY_EPSILON, max_dist).is_some();
+        self.unbounded.iter().any(|&index| blocks(index))
+            || self.bvh.any_hit(ray, max_dist, |item| blocks(self.bounded[item]))
+    }
+}
+
+/// Mirrors `dir` about the surface with normal `normal`.
+pub fn reflect(dir: &Vector3<f64>, normal: &Vector3<f64>) -> Vector3<f64> {
+    dir - 2.0 * dir.dot(normal) * normal
+}
+
+/// Bends `dir` through a surface by Snell's law, `normal` faces back along `dir` and `eta` is the
+/// ratio of the indices of refraction on the incoming side over the outgoing one. `None` means the
+/// ray is totally internally reflected instead.
+pub fn refract(dir: &Vector3<f64>, normal: &Vector3<f64>, eta: f64) -> Option<Vector3<f64>> {
+    let cos_i = -dir.dot(normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
     }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(dir * eta + normal * (eta * cos_i - cos_t))
+}
+
+/// Schlick's approximation of how much light is reflected rather than refracted, 1 past the
+/// critical angle.
+pub fn schlick(cos_i: f64, eta: f64) -> f64 {
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    //Going into a less dense material the angle on the far side is the one that matters
+    let cos = if eta > 1.0 {
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        (1.0 - sin2_t).sqrt()
+    } else {
+        cos_i
+    };
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+/// Seeds for each tile's rng, mixing the tile in so tiles don't repeat each other's samples and
+/// the seed in so different seeds don't just swap tiles' samples around. Seed 0 seeds each tile
+/// with its own index.
+fn tile_seed(seed: u64, tile: usize) -> u64 {
+    seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ tile as u64
+}
+
+/// Unit vectors pointing right and up across the image, after any roll.
+fn camera_basis(camera: &Camera) -> (Vector3<f64>, Vector3<f64>) {
+    let unrolled_right: Vector3<f64> = camera.up.cross(&camera.dir).normalize();
+    let unrolled_up: Vector3<f64> = camera.dir.cross(&unrolled_right).normalize();
+    let (sin_roll, cos_roll) = camera.roll.to_radians().sin_cos();
+    let vp_right: Vector3<f64> = unrolled_right * cos_roll - unrolled_up * sin_roll;
+    let vp_up: Vector3<f64> = unrolled_up * cos_roll + unrolled_right * sin_roll;
+    (vp_right, vp_up)
 }
 
 fn screen_to_coord_stride(width: f64, height: f64, camera: &Camera) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
-    let screen_pos: Vector3<f64> = &camera.dir + &camera.pos;
+    let screen_pos: Vector3<f64> = camera.dir + camera.pos;
     // println!("Screen pos: {:?}",screen_pos);
-    let vp_right: Vector3<f64> = Vector3::new(0.0, 1.0, 0.0).cross(&camera.dir).normalize();
+    let (vp_right, vp_up) = camera_basis(camera);
     // println!("Right: {:?}",vp_right);
-    let vp_up: Vector3<f64> = camera.dir.cross(&vp_right).normalize();
     // println!("Up: {:?}",vp_up);
     let half_width_geom = camera.dir.magnitude() * (camera.fov.to_radians() / 2.0).tan();
     // println!("Half width: {}",half_width_geom);
@@ -229,10 +521,10 @@ fn screen_to_coord_stride(width: f64, height: f64, camera: &Camera) -> (Vector3<
     // println!("Half height: {}",half_height_geom);
     let left_side_geom: Vector3<f64> = screen_pos - (vp_right * half_width_geom);
     // println!("Left side middle pos: {:?}",left_side_geom);
-    let x_pixel_stride_right: Vector3<f64> = &vp_right * (half_width_geom / width * 2.0);
+    let x_pixel_stride_right: Vector3<f64> = vp_right * (half_width_geom / width * 2.0);
     // println!("Vector to add per x pixel: {:?}",x_pixel_stride_right);
     //Doing a minus here
-    let y_pixel_stride_down: Vector3<f64> = &vp_up * ((half_height_geom * -2.0) / height);
+    let y_pixel_stride_down: Vector3<f64> = vp_up * ((half_height_geom * -2.0) / height);
     // println!("Vector to add per y pixel: {:?}",y_pixel_stride_down);
     let top_left: Vector3<f64> = left_side_geom + vp_up * half_height_geom;
     // println!("Vector top left: {:?}",top_left);
@@ -246,16 +538,26 @@ mod tests {
 
     use approx;
     use nalgebra::Vector3;
-
-    use crate::tracer::{Camera, screen_to_coord_stride};
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use crate::Resolution;
+    use crate::buffer::FrameBuffer;
+    use crate::tracer::aov::Aov;
+    use crate::tracer::{Camera, Ray, SceneState, RAY_EPSILON, refract, schlick, screen_to_coord_stride};
+    use crate::tracer::colour::Colour;
+    use crate::tracer::geom::Drawable;
+    use crate::tracer::geom::plane::Plane;
+    use crate::tracer::geom::sphere::Sphere;
+    use crate::tracer::geom::triangle::Triangle;
+    use crate::tracer::lens::{Aperture, ThinLens};
+    use crate::tracer::light::{AreaLight, AreaShape, DirectionalLight, Light, PointLight};
+    use crate::tracer::path::PathTracer;
+    use crate::tracer::sampler::SamplerType;
 
     #[test]
     fn test_screen_coords() {
-        let at_orig_cam = Camera {
-            pos: Vector3::new(0.0, 0.0, -1.0),
-            dir: Vector3::new(0.0, 0.0, 1.0),
-            fov: 90.0,
-        };
+        let at_orig_cam = Camera::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0), 90.0);
         let (top_left, x_pixel, y_pixel) = screen_to_coord_stride(100.0, 100.0, &at_orig_cam);
         let angle: f64 = 45.0;
         let tan_of_a = angle.to_radians().tan();
@@ -271,11 +573,7 @@ mod tests {
         approx::assert_ulps_eq!( -2.0 / tan_of_a, y_pixel[1] * 100.0);
         approx::assert_ulps_eq!( 0.0, y_pixel[2] * 100.0);
 
-        let first_cam = Camera {
-            pos: Vector3::new(0.0, 0.0, 0.0),
-            dir: Vector3::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0),
-            fov: 90.0,
-        };
+        let first_cam = Camera::new(Vector3::zeros(), Vector3::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0), 90.0);
 
         let (top_left, x_pixel, y_pixel) = screen_to_coord_stride(100.0, 100.0, &first_cam);
         approx::assert_ulps_eq!(0.0, top_left[0]);
@@ -291,4 +589,302 @@ mod tests {
         approx::assert_ulps_eq!( -2.0 * cos_of_a, y_pixel[1] * 100.0);
         approx::assert_ulps_eq!( 0.0, y_pixel[2] * 100.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_camera_up_and_roll() {
+        //looking straight down with the default up has no right to speak of
+        let down = Camera::new(Vector3::zeros(), Vector3::new(0.0, -1.0, 0.0), 90.0);
+        assert!(down.degenerate().is_some());
+        let down = Camera::look_at(Vector3::zeros(), Vector3::new(0.0, -2.0, 0.0), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        assert!(down.degenerate().is_none());
+        let (top_left, x_pixel, y_pixel) = screen_to_coord_stride(100.0, 100.0, &down);
+        approx::assert_ulps_eq!(top_left, Vector3::new(-2.0, -2.0, 2.0));
+        approx::assert_ulps_eq!(x_pixel * 100.0, Vector3::new(4.0, 0.0, 0.0));
+        approx::assert_ulps_eq!(y_pixel * 100.0, Vector3::new(0.0, 0.0, -4.0));
+
+        let mut rolled = Camera::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        rolled.roll = 90.0;
+        let (top_left, x_pixel, y_pixel) = screen_to_coord_stride(100.0, 100.0, &rolled);
+        approx::assert_abs_diff_eq!(top_left, Vector3::new(1.0, 1.0, 1.0), epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(x_pixel * 100.0, Vector3::new(0.0, -2.0, 0.0), epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(y_pixel * 100.0, Vector3::new(-2.0, 0.0, 0.0), epsilon = 1e-12);
+    }
+
+    fn test_scene() -> SceneState {
+        SceneState::new(
+            vec![
+                Box::new(Sphere::new(Vector3::new(0.0, 0.0, 4.0), 1.5, Colour::new(0.78, 0.0, 0.78), 0.6, 0.8, 2.0)),
+                Box::new(Plane::new(Vector3::new(0.0, -2.0, 0.0), Vector3::new(0.0, 1.0, 0.0),
+                                    Colour::white(), 0.8, 0.2, 2.0)),
+            ],
+            vec![Box::new(PointLight {
+                pos: Vector3::new(-3.0, 3.0, 0.0),
+                colour: Colour::white(),
+                intensity: 0.7,
+            })],
+            Camera::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0), 90.0),
+            0.1,
+            Colour::black(),
+        )
+    }
+
+    //Lots of small overlapping spheres and triangles so the BVH gets a few levels deep
+    fn crowded_scene() -> SceneState {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut geom: Vec<Box<dyn Drawable>> = vec![];
+        for _ in 0..60 {
+            let pos = Vector3::new(rng.gen_range(-4.0, 4.0), rng.gen_range(-3.0, 3.0), rng.gen_range(3.0, 10.0));
+            let colour = Colour::new(rng.gen(), rng.gen(), rng.gen());
+            geom.push(Box::new(Sphere::new(pos, rng.gen_range(0.2, 0.8), colour, 0.6, 0.4, 4.0)));
+            let a = Vector3::new(rng.gen_range(-4.0, 4.0), rng.gen_range(-3.0, 3.0), rng.gen_range(3.0, 10.0));
+            let b = a + Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+            let c = a + Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+            geom.push(Box::new(Triangle::new(a, b, c, colour, 0.8, 0.2, 2.0)));
+        }
+        geom.push(Box::new(Plane::new(Vector3::new(0.0, -3.0, 0.0), Vector3::new(0.0, 1.0, 0.0),
+                                      Colour::white(), 0.8, 0.2, 2.0)));
+        let light = PointLight { pos: Vector3::new(2.0, 5.0, 0.0), colour: Colour::white(), intensity: 0.8 };
+        let camera = Camera::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        SceneState::new(geom, vec![Box::new(light)], camera, 0.1, Colour::black())
+    }
+
+    #[test]
+    fn test_extent() {
+        let scene = test_scene();
+        let extent = scene.extent().unwrap();
+        assert_eq!(extent.min, Vector3::new(-1.5, -1.5, 2.5));
+        assert_eq!(extent.max, Vector3::new(1.5, 1.5, 5.5));
+        assert_eq!(scene.unbounded_count(), 1);
+    }
+
+    #[test]
+    fn test_bvh_matches_brute_force() {
+        let scene = crowded_scene();
+        assert!(scene.bvh.node_count() > 1);
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..2000 {
+            let orig = Vector3::new(rng.gen_range(-5.0, 5.0), rng.gen_range(-2.0, 4.0), rng.gen_range(-1.0, 11.0));
+            let dir = Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+            let ray = Ray::new(orig, dir);
+
+            //every object in order, planes included, earlier objects winning ties
+            let mut linear: Option<(f64, usize)> = None;
+            for (index, object) in scene.geom.iter().enumerate() {
+                if let Some(hit) = object.intersect(&ray, RAY_EPSILON, f64::INFINITY) {
+                    if linear.is_none_or(|(closest, _)| hit.dist < closest) {
+                        linear = Some((hit.dist, index));
+                    }
+                }
+            }
+            let found = scene.closest_hit(&ray).map(|info| (info.dist, info.object));
+            assert_eq!(found, linear);
+
+            let max_dist = rng.gen_range(0.5, 8.0);
+            let blocked = scene.geom.iter().any(|object| object.intersect(&ray, RAY_EPSILON, max_dist).is_some());
+            assert_eq!(scene.occluded(&ray, max_dist), blocked);
+        }
+    }
+
+    #[test]
+    fn test_rasterise_thread_count_independent() {
+        let mut scene = test_scene();
+        let res = Resolution { width: 70, height: 45 };
+        let render = |scene: &SceneState, threads: u8| {
+            let mut frame = vec![0; 70 * 45 * 4];
+            scene.rasterise(&mut frame, &res, 4, threads);
+            frame
+        };
+        for sampler in ["independent", "stratified", "halton", "sobol"].iter() {
+            scene.sampler = sampler.parse::<SamplerType>().unwrap().sampler();
+            let single = render(&scene, 1);
+            assert!(single.iter().any(|&channel| channel != 0 && channel != 255));
+            assert_eq!(single, render(&scene, 4));
+        }
+
+        //another seed moves the samples, but is just as repeatable
+        let first_seed = render(&scene, 3);
+        scene.seed = 7;
+        let second_seed = render(&scene, 1);
+        assert_ne!(first_seed, second_seed);
+        assert_eq!(second_seed, render(&scene, 3));
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        let mut scene = test_scene();
+        let res = Resolution { width: 32, height: 24 };
+        let mut pinhole = vec![0; 32 * 24 * 4];
+        scene.rasterise(&mut pinhole, &res, 8, 1);
+
+        //focused well behind the sphere its silhouette smears into the floor and background
+        scene.camera.lens = Some(ThinLens { radius: 0.4, focus_dist: 20.0, aperture: Aperture::Polygon(5) });
+        let mut blurred = vec![0; 32 * 24 * 4];
+        let mut blurred_multi = vec![0; 32 * 24 * 4];
+        scene.rasterise(&mut blurred, &res, 8, 1);
+        scene.rasterise(&mut blurred_multi, &res, 8, 3);
+        assert_eq!(blurred, blurred_multi);
+        assert_ne!(blurred, pinhole);
+        //but the middle of the sphere only sees more of the sphere
+        let centre = (12 * 32 + 16) * 4;
+        for channel in centre..centre + 4 {
+            assert!((blurred[channel] as i32 - pinhole[channel] as i32).abs() <= 8);
+        }
+    }
+
+    #[test]
+    fn test_directional_shadows() {
+        let mut scene = test_scene();
+        scene.lights = vec![Box::new(DirectionalLight {
+            dir: Vector3::new(0.0, -1.0, 0.0),
+            colour: Colour::white(),
+            intensity: 0.5,
+        })];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut down = |x: f64, z: f64| {
+            scene.trace(&Ray::new(Vector3::new(x, -1.9, z), Vector3::new(0.0, -1.0, 0.0)), None, &mut rng)
+        };
+        //straight under the sphere is in its shadow, and the sun lights everywhere else alike
+        assert_eq!(down(0.0, 4.0), Colour::new(0.1, 0.1, 0.1));
+        let lit = down(3.0, 4.0);
+        assert_eq!(lit, down(-50.0, 300.0));
+        assert!(lit.r > 0.5);
+    }
+
+    #[test]
+    fn test_area_light_penumbra() {
+        let mut scene = test_scene();
+        scene.lights = vec![Box::new(AreaLight {
+            shape: AreaShape::Disk { centre: Vector3::new(0.0, 6.0, 4.0), normal: Vector3::new(0.0, -1.0, 0.0), radius: 2.0 },
+            colour: Colour::white(),
+            intensity: 0.5,
+            samples: 64,
+        })];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut down = |x: f64| {
+            scene.trace(&Ray::new(Vector3::new(x, -1.9, 4.0), Vector3::new(0.0, -1.0, 0.0)), None, &mut rng).r
+        };
+        //fully shadowed under the sphere, fully lit well clear of it, and part way in between
+        let (umbra, penumbra, lit) = (down(0.0), down(1.6), down(6.0));
+        assert_eq!(umbra, 0.1);
+        assert!(penumbra > umbra + 0.05 && penumbra < lit - 0.05);
+
+        //looking up from the floor at the light sees the light itself
+        let ray = Ray::new(Vector3::new(3.0, -1.9, 4.0), Vector3::new(-0.2, 1.0, 0.0));
+        assert_eq!(scene.trace(&ray, None, &mut rng), Colour::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_path_tracing_bounces_light() {
+        let mut scene = test_scene();
+        scene.lights = vec![Box::new(DirectionalLight {
+            dir: Vector3::new(0.0, -1.0, 0.0),
+            colour: Colour::white(),
+            intensity: 1.0,
+        })];
+        scene.ambient = 0.0;
+        let mut rng = StdRng::seed_from_u64(0);
+        let under_sphere = Ray::new(Vector3::new(0.0, -1.9, 4.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(scene.trace(&under_sphere, None, &mut rng), Colour::black());
+
+        scene.integrator = Box::new(PathTracer);
+        //sunlit floor looks the same as with Phong shading, plus whatever bounces back
+        let open_floor = Ray::new(Vector3::new(3.0, -1.9, 4.0), Vector3::new(0.0, -1.0, 0.0));
+        let lit = scene.trace(&open_floor, None, &mut rng);
+        assert!(lit.r >= 1.0 && lit.g >= 1.0);
+        //only light bounced off the floor and back down from the sphere reaches under it
+        let mut bounced = Colour::black();
+        for _ in 0..200 {
+            bounced += scene.trace(&under_sphere, None, &mut rng);
+        }
+        assert!(bounced.r > 0.0 && bounced.r / 200.0 < 0.5);
+    }
+
+    #[test]
+    fn test_path_and_phong_agree_on_lights() {
+        let floor = Plane::new(Vector3::zeros(), Vector3::new(0.0, 1.0, 0.0), Colour::new(1.0, 0.8, 0.6), 0.9, 0.0, 2.0);
+        let lights: Vec<Box<dyn Light>> = vec![
+            Box::new(PointLight { pos: Vector3::new(1.0, 3.0, 0.0), colour: Colour::new(1.0, 0.2, 0.2), intensity: 0.8 }),
+            Box::new(DirectionalLight { dir: Vector3::new(-1.0, -2.0, 0.5), colour: Colour::new(0.1, 0.3, 1.0), intensity: 0.6 }),
+        ];
+        let camera = Camera::new(Vector3::new(0.0, 2.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 90.0);
+        let mut scene = SceneState::new(vec![Box::new(floor)], lights, camera, 0.0, Colour::black());
+        //only the light arriving straight from the two lights, nothing bounced
+        scene.max_depth = 0;
+        let down = Ray::new(Vector3::new(0.0, 2.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let mut rng = StdRng::seed_from_u64(1);
+        let phong = scene.trace(&down, None, &mut rng);
+        scene.integrator = Box::new(PathTracer);
+        let path = scene.trace(&down, None, &mut rng);
+        approx::assert_abs_diff_eq!(phong.r, path.r, epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(phong.g, path.g, epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(phong.b, path.b, epsilon = 1e-12);
+        //both tinted by the lights and averaged over them rather than added up
+        assert!(path.r > path.b && path.r < 0.9 * 0.8);
+    }
+
+    #[test]
+    fn test_refract_and_fresnel() {
+        //straight through glass nothing bends and only a little is reflected
+        let dir = Vector3::new(0.0, 0.0, 1.0);
+        let normal = Vector3::new(0.0, 0.0, -1.0);
+        let refracted = refract(&dir, &normal, 1.0 / 1.5).unwrap();
+        approx::assert_ulps_eq!(refracted, dir);
+        approx::assert_ulps_eq!(schlick(1.0, 1.0 / 1.5), 0.04, max_ulps = 5);
+
+        //leaving glass at 60 degrees is past the critical angle
+        let dir = Vector3::new(60.0_f64.to_radians().sin(), 0.0, 60.0_f64.to_radians().cos());
+        assert!(refract(&dir, &normal, 1.5).is_none());
+        assert_eq!(schlick(-dir.dot(&normal), 1.5), 1.0);
+    }
+
+    #[test]
+    fn test_mirror_reflects() {
+        let mut mirror = Plane::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0),
+                                    Colour::black(), 0.8, 0.0, 2.0);
+        mirror.material.reflectivity = 1.0;
+        //behind the camera, so only visible in the mirror
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, -3.0), 1.0, Colour::new(1.0, 0.0, 0.0), 1.0, 0.0, 2.0);
+        let light = PointLight { pos: Vector3::new(0.0, 0.0, 1.0), colour: Colour::white(), intensity: 1.0 };
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        let mut scene = SceneState::new(vec![Box::new(mirror), Box::new(sphere)], vec![Box::new(light)], camera, 0.1,
+                                        Colour::new(0.0, 0.0, 1.0));
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+
+        let colour = scene.trace(&ray, None, &mut StdRng::seed_from_u64(0));
+        assert!(colour.r > 0.8);
+        assert_eq!((colour.g, colour.b), (0.0, 0.0));
+
+        scene.max_depth = 0;
+        let colour = scene.trace(&ray, None, &mut StdRng::seed_from_u64(0));
+        assert_eq!(colour, Colour::black());
+    }
+
+    #[test]
+    fn test_aovs() {
+        let scene = test_scene();
+        let aovs = [Aov::Depth, Aov::Normal, Aov::ObjectId, Aov::MaterialId, Aov::Albedo, Aov::Direct, Aov::Ambient];
+        let frame = FrameBuffer::with_aovs(Resolution { width: 8, height: 6 }, &aovs);
+        scene.render(&frame, 1, 2);
+        let value = |aov: Aov, x: usize, y: usize| frame.aov(aov).unwrap().read().unwrap()[y * 8 + x];
+
+        //just off straight ahead is the front of the sphere
+        approx::assert_abs_diff_eq!(value(Aov::Depth, 4, 3).r, 3.5, epsilon = 0.1);
+        approx::assert_abs_diff_eq!(value(Aov::Normal, 4, 3).b, -1.0, epsilon = 0.05);
+        assert_eq!(value(Aov::ObjectId, 4, 3).r, 0.0);
+        assert_eq!(value(Aov::Albedo, 4, 3), Colour::new(0.78, 0.0, 0.78));
+        //the floor along the bottom, nothing at the top
+        assert_eq!(value(Aov::ObjectId, 0, 5).r, 1.0);
+        assert_eq!(value(Aov::MaterialId, 0, 5).r, 1.0);
+        assert_eq!(value(Aov::ObjectId, 0, 0).r, -1.0);
+        assert_eq!(value(Aov::Depth, 0, 0).r, f64::INFINITY);
+
+        //nothing is reflective so the beauty is just direct plus ambient
+        let radiance = frame.radiance().read().unwrap();
+        for y in 0..6 {
+            for x in 0..8 {
+                let sum = value(Aov::Direct, x, y) + value(Aov::Ambient, x, y);
+                approx::assert_abs_diff_eq!(sum.r, radiance[y * 8 + x].r, epsilon = 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use nalgebra::Vector3;
+use rand::RngCore;
+
+use crate::tracer::{Ray, SceneState};
+use crate::tracer::aov::AovSample;
+use crate::tracer::colour::Colour;
+use crate::tracer::integrator::Integrator;
+use crate::tracer::path::cosine_hemisphere;
+
+pub const DEFAULT_AO_SAMPLES: u32 = 16;
+pub const DEFAULT_AO_DISTANCE: f64 = 1.0;
+
+/// How much of the hemisphere above a point is open, found by sending rays out over it and
+/// counting the ones that get `max_dist` without hitting anything.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: u32,
+    /// Anything further away doesn't occlude, in scene units.
+    pub max_dist: f64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> AmbientOcclusion {
+        AmbientOcclusion { samples: DEFAULT_AO_SAMPLES, max_dist: DEFAULT_AO_DISTANCE }
+    }
+}
+
+impl AmbientOcclusion {
+    /// Fraction of the hemisphere around `normal` that's open, 1 for an unobstructed surface and
+    /// 0 at the bottom of a crevice. Rays are cosine weighted, so occluders overhead count for
+    /// more than ones off to the side, as they would for diffuse light.
+    pub fn visibility(&self, scene: &SceneState, point: &Vector3<f64>, normal: &Vector3<f64>, rng: &mut dyn RngCore) -> f64 {
+        let open = (0..self.samples)
+            .filter(|_| !scene.occluded(&Ray::new(*point, cosine_hemisphere(normal, rng)), self.max_dist))
+            .count();
+        open as f64 / self.samples as f64
+    }
+}
+
+/// A clay render, every surface white and shaded only by its ambient occlusion. Uses the
+/// scene's `ambient_occlusion` settings, or the defaults if it hasn't any.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Clay;
+
+impl Integrator for Clay {
+    fn name(&self) -> &str {
+        "ao"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+        let hit_info = match scene.closest_hit(ray) {
+            Some(info) => info,
+            None => return Colour::white()
+        };
+        let occlusion = scene.ambient_occlusion.unwrap_or_default();
+        let visibility = occlusion.visibility(scene, &hit_info.point, &hit_info.normal, rng);
+        let colour = Colour::new(visibility, visibility, visibility);
+        if let Some(aov) = aov {
+            *aov = AovSample {
+                depth: hit_info.dist,
+                normal: hit_info.normal,
+                albedo: Colour::white(),
+                object_id: Some(hit_info.object),
+                material_id: Some(scene.material_id(hit_info.object)),
+                direct: Colour::black(),
+                ambient: colour,
+            };
+        }
+        colour
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::tracer::{Camera, Ray, SceneState};
+    use crate::tracer::colour::Colour;
+    use crate::tracer::geom::plane::Plane;
+    use crate::tracer::geom::sphere::Sphere;
+    use crate::tracer::integrator::Integrator;
+    use crate::tracer::occlusion::{AmbientOcclusion, Clay};
+
+    //A sphere resting on a floor, the floor round where they touch is a crevice
+    fn scene() -> SceneState {
+        let floor = Plane::new(Vector3::zeros(), Vector3::new(0.0, 1.0, 0.0), Colour::white(), 1.0, 0.0, 2.0);
+        let sphere = Sphere::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Colour::white(), 1.0, 0.0, 2.0);
+        let camera = Camera::new(Vector3::new(0.0, 1.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 90.0);
+        SceneState::new(vec![Box::new(floor), Box::new(sphere)], vec![], camera, 0.5, Colour::black())
+    }
+
+    #[test]
+    fn test_visibility() {
+        let scene = scene();
+        let mut rng = StdRng::seed_from_u64(2);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let ao = AmbientOcclusion { samples: 256, max_dist: 1.0 };
+        assert_eq!(ao.visibility(&scene, &Vector3::new(5.0, 0.0, 0.0), &up, &mut rng), 1.0);
+        let crevice = ao.visibility(&scene, &Vector3::new(0.3, 0.0, 0.0), &up, &mut rng);
+        assert!(crevice > 0.0 && crevice < 0.5);
+        //the sphere is out of reach with a short enough distance
+        let short = AmbientOcclusion { samples: 256, max_dist: 0.01 };
+        assert_eq!(short.visibility(&scene, &Vector3::new(0.3, 0.0, 0.0), &up, &mut rng), 1.0);
+    }
+
+    #[test]
+    fn test_clay() {
+        let mut scene = scene();
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut trace = |scene: &SceneState, x: f64| {
+            Clay.radiance(scene, &Ray::new(Vector3::new(x, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0)), None, &mut rng)
+        };
+        //the sphere is in reach of the default distance from the floor just beside it, but not
+        //from further out
+        assert_eq!(trace(&scene, -5.0), Colour::white());
+        let beside = trace(&scene, -1.2);
+        assert!(beside.r > 0.5 && beside.r < 1.0);
+        assert_eq!((beside.r, beside.g), (beside.g, beside.b));
+        scene.ambient_occlusion = Some(AmbientOcclusion { samples: 64, max_dist: 0.5 });
+        assert_eq!(trace(&scene, -1.2), Colour::white());
+    }
+
+    #[test]
+    fn test_ambient_term() {
+        let mut scene = scene();
+        //skimming in just above the floor to where it's under the sphere
+        let under_sphere = Ray::new(Vector3::new(3.0, 0.02, 0.0), Vector3::new(-2.7, -0.02, 0.0));
+        let mut rng = StdRng::seed_from_u64(2);
+        //no lights, so the flat ambient term is all there is
+        assert_eq!(scene.trace(&under_sphere, None, &mut rng), Colour::new(0.5, 0.5, 0.5));
+        scene.ambient_occlusion = Some(AmbientOcclusion { samples: 64, max_dist: 1.0 });
+        let occluded = scene.trace(&under_sphere, None, &mut rng);
+        assert!(occluded.r < 0.25);
+    }
+}
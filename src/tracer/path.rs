@@ -0,0 +1,233 @@
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+use rand::prelude::*;
+
+use crate::tracer::{HitInformation, Ray, SceneState, reflect, refract, schlick};
+use crate::tracer::aov::AovSample;
+use crate::tracer::colour::{Colour, Material};
+use crate::tracer::integrator::Integrator;
+use crate::tracer::light::perpendiculars;
+
+//Bounces a path always takes before Russian roulette can end it
+const ROULETTE_DEPTH: u32 = 3;
+//Even the brightest paths have this chance of being ended by Russian roulette
+const MAX_SURVIVAL: f64 = 0.95;
+
+/// Monte Carlo path tracing, with bounced light from every surface in place of the ambient term.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn name(&self) -> &str {
+        "path"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+        trace(scene, ray, aov, rng)
+    }
+}
+
+/// Follows a path of bounces from the camera, adding the light found at each surface and
+/// choosing where to go next by the surface's material. Mirror and glass bounces happen as
+/// often as the material's reflectivity and transparency, diffuse and glossy ones the rest of
+/// the time. Light from the scene's lights is sampled at every diffuse or glossy surface, so
+/// lights with a surface only count when seen directly, or in a mirror or through glass.
+fn trace(scene: &SceneState, camera_ray: &Ray, mut aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+    let mut radiance = Colour::black();
+    let mut throughput = Colour::white();
+    let mut ray = Ray { orig: camera_ray.orig, dir: camera_ray.dir };
+    let mut count_emitted = true;
+
+    for depth in 0..=scene.max_depth {
+        let hit_info = scene.closest_hit(&ray);
+        let hit_dist = hit_info.as_ref().map_or(f64::INFINITY, |info| info.dist);
+        if let Some((light, dist)) = scene.closest_emitter(&ray, hit_dist) {
+            if count_emitted {
+                radiance += throughput * light.emitted();
+            }
+            if let Some(aov) = aov.take() {
+                *aov = AovSample { depth: dist, direct: light.emitted(), ..AovSample::default() };
+            }
+            break;
+        }
+        let hit_info = match hit_info {
+            Some(info) => info,
+            None => {
+                radiance += throughput * scene.background_colour;
+                break;
+            }
+        };
+
+        let material = hit_info.material;
+        let opaque = (1.0 - material.reflectivity - material.transparency).max(0.0);
+        let choice: f64 = rng.gen();
+        let diffuse_or_glossy = choice >= material.reflectivity + material.transparency;
+        let first_aov = if depth == 0 { aov.take() } else { None };
+        if diffuse_or_glossy || first_aov.is_some() {
+            let direct = direct_light(scene, &hit_info, &ray, rng);
+            if diffuse_or_glossy {
+                radiance += throughput * direct;
+            }
+            if let Some(aov) = first_aov {
+                *aov = AovSample {
+                    depth: hit_info.dist,
+                    normal: hit_info.normal,
+                    albedo: material.colour,
+                    object_id: Some(hit_info.object),
+                    material_id: Some(scene.material_id(hit_info.object)),
+                    direct: direct * opaque,
+                    ambient: Colour::black(),
+                };
+            }
+        }
+        if depth == scene.max_depth {
+            break;
+        }
+
+        let dir = if choice < material.reflectivity {
+            count_emitted = true;
+            reflect(&ray.dir, &hit_info.normal)
+        } else if !diffuse_or_glossy {
+            count_emitted = true;
+            let eta = if hit_info.front_face { 1.0 / material.ior } else { material.ior };
+            let fresnel = schlick(-ray.dir.dot(&hit_info.normal), eta);
+            match refract(&ray.dir, &hit_info.normal, eta) {
+                Some(dir) if rng.gen::<f64>() >= fresnel => dir,
+                _ => reflect(&ray.dir, &hit_info.normal),
+            }
+        } else {
+            count_emitted = false;
+            match scatter(material, &hit_info.normal, &ray.dir, rng) {
+                Some((dir, weight)) => {
+                    throughput = throughput * weight;
+                    dir
+                }
+                None => break
+            }
+        };
+        ray = Ray::new(hit_info.point, dir);
+
+        if depth + 1 >= ROULETTE_DEPTH {
+            let survival = throughput.max_channel().min(MAX_SURVIVAL);
+            if rng.gen::<f64>() >= survival {
+                break;
+            }
+            throughput = throughput / survival;
+        }
+    }
+    radiance
+}
+
+/// Light reaching the hit straight from the scene's lights and reflected back along the ray,
+/// following Phong shading's convention so the two integrators agree on the same scene: each
+/// light lights in its own colour, and the result is averaged over the lights that can see the
+/// hit point rather than added up. Light intensities are scaled so a white diffuse surface
+/// facing a light looks the same as it does with Phong shading.
+fn direct_light(scene: &SceneState, hit_info: &HitInformation, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+    let material = hit_info.material;
+    let mut direct = Colour::black();
+    let mut lit_by = 0;
+    for light in &scene.lights {
+        let samples = light.samples();
+        let mut from_light = Colour::black();
+        let mut visible = false;
+        for _ in 0..samples {
+            let light = match light.sample(&hit_info.point, rng) {
+                Some(light) => light,
+                None => continue
+            };
+            let to_light = Ray::new(hit_info.point, light.to_light);
+            if scene.occluded(&to_light, light.dist) {
+                continue;
+            }
+            visible = true;
+            let cos = to_light.dir.dot(&hit_info.normal);
+            if cos <= 0.0 {
+                continue;
+            }
+            let light_reflect = reflect(&to_light.dir, &hit_info.normal);
+            let glossy = light_reflect.dot(&ray.dir).max(0.0).powf(material.specular_exp) * material.specular;
+            from_light += material.colour * light.colour * ((material.diffuse + glossy) * cos * light.intensity / samples as f64);
+        }
+        if visible {
+            direct += from_light;
+            lit_by += 1;
+        }
+    }
+    if lit_by > 0 {
+        direct = direct / lit_by as f64;
+    }
+    direct
+}
+
+/// Picks a diffuse or glossy direction to bounce in, with the surface's reflectance over the
+/// chance of picking that direction. `None` ends the path, when the material reflects nothing or
+/// the glossy lobe points into the surface.
+fn scatter(material: &Material, normal: &Vector3<f64>, incoming: &Vector3<f64>, rng: &mut dyn RngCore)
+           -> Option<(Vector3<f64>, Colour)> {
+    let total = material.diffuse + material.specular;
+    if total <= 0.0 {
+        return None;
+    }
+    let diffuse_chance = material.diffuse / total;
+    if rng.gen::<f64>() < diffuse_chance {
+        //cosine weighted, which cancels the cosine and the 1/pi of a Lambertian surface
+        let dir = cosine_hemisphere(normal, rng);
+        return Some((dir, material.colour * (material.diffuse / diffuse_chance)));
+    }
+
+    //Phong lobe around the mirror direction
+    let mirror = reflect(incoming, normal);
+    let (lobe_across, lobe_up) = perpendiculars(&mirror);
+    let cos_alpha = rng.gen::<f64>().powf(1.0 / (material.specular_exp + 1.0));
+    let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+    let (sin, cos) = (2.0 * PI * rng.gen::<f64>()).sin_cos();
+    let dir = (lobe_across * cos + lobe_up * sin) * sin_alpha + mirror * cos_alpha;
+    let cos_normal = dir.dot(normal);
+    if cos_normal <= 0.0 {
+        return None;
+    }
+    let weight = material.specular * 2.0 * cos_normal / (material.specular_exp + 1.0) / (1.0 - diffuse_chance);
+    Some((dir, material.colour * weight))
+}
+
+/// A direction in the hemisphere around `normal`, more likely the closer it is to `normal` in
+/// proportion to the cosine between them.
+pub(crate) fn cosine_hemisphere(normal: &Vector3<f64>, rng: &mut dyn RngCore) -> Vector3<f64> {
+    let (across, up) = perpendiculars(normal);
+    let radius = rng.gen::<f64>().sqrt();
+    let (sin, cos) = (2.0 * PI * rng.gen::<f64>()).sin_cos();
+    let height = (1.0 - radius * radius).max(0.0).sqrt();
+    (across * cos + up * sin) * radius + normal * height
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::tracer::colour::{Colour, Material};
+    use crate::tracer::path::scatter;
+
+    #[test]
+    fn test_scatter_stays_above_surface() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let matte = Material::new(Colour::white(), 0.5, 0.0, 2.0);
+        let shiny = Material::new(Colour::white(), 0.0, 0.5, 200.0);
+        for _ in 0..200 {
+            let (dir, weight) = scatter(&matte, &normal, &incoming, &mut rng).unwrap();
+            approx::assert_abs_diff_eq!(dir.norm(), 1.0, epsilon = 1e-9);
+            assert!(dir[1] >= 0.0);
+            assert_eq!(weight, Colour::new(0.5, 0.5, 0.5));
+            //a tight lobe stays close to the mirror direction
+            if let Some((dir, _)) = scatter(&shiny, &normal, &incoming, &mut rng) {
+                assert!(dir.dot(&Vector3::new(1.0, 1.0, 0.0).normalize()) > 0.95);
+            }
+        }
+        assert!(scatter(&Material::new(Colour::white(), 0.0, 0.0, 2.0), &normal, &incoming, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,178 @@
+use nalgebra::Vector3;
+use rand::RngCore;
+
+use crate::tracer::{HitInformation, Ray, SceneState, reflect, refract, schlick};
+use crate::tracer::aov::AovSample;
+use crate::tracer::colour::Colour;
+use crate::tracer::integrator::Integrator;
+
+/// Phong shading from the lights with shadow rays and a flat ambient term, following mirror
+/// reflections and refractions until the scene's `max_depth`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Phong;
+
+impl Integrator for Phong {
+    fn name(&self) -> &str {
+        "phong"
+    }
+
+    fn radiance(&self, scene: &SceneState, ray: &Ray, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+        cast_ray(scene, ray, 0, aov, rng)
+    }
+}
+
+/// Colour seen along the ray, following mirror reflections and refractions until `depth`
+/// reaches the scene's `max_depth`. Lights with a surface are seen where they're nearer than any
+/// object.
+fn cast_ray(scene: &SceneState, ray: &Ray, depth: u32, aov: Option<&mut AovSample>, rng: &mut dyn RngCore) -> Colour {
+    let hit_info = scene.closest_hit(ray);
+    let hit_dist = hit_info.as_ref().map_or(f64::INFINITY, |info| info.dist);
+    if let Some((light, dist)) = scene.closest_emitter(ray, hit_dist) {
+        if let Some(aov) = aov {
+            *aov = AovSample { depth: dist, direct: light.emitted(), ..AovSample::default() };
+        }
+        return light.emitted();
+    }
+    let hit_info = match hit_info {
+        Some(info) => info,
+        None => return scene.background_colour
+    };
+    let material = hit_info.material;
+    let recurse = depth < scene.max_depth && (material.reflectivity > 0.0 || material.transparency > 0.0);
+    //once out of bounces the surface's own shading is all that's left
+    let local_weight = if recurse {
+        (1.0 - material.reflectivity - material.transparency).max(0.0)
+    } else {
+        1.0
+    };
+    let (ambient, direct) = colour_for_hit(scene, &hit_info, ray, rng);
+    let local_colour = ambient + direct;
+    if let Some(aov) = aov {
+        *aov = AovSample {
+            depth: hit_info.dist,
+            normal: hit_info.normal,
+            albedo: material.colour,
+            object_id: Some(hit_info.object),
+            material_id: Some(scene.material_id(hit_info.object)),
+            direct: direct * local_weight,
+            ambient: ambient * local_weight,
+        };
+    }
+    if !recurse {
+        return local_colour;
+    }
+
+    let mut reflect_weight = material.reflectivity;
+    let mut refracted: Option<(Vector3<f64>, f64)> = None;
+    if material.transparency > 0.0 {
+        let eta = if hit_info.front_face { 1.0 / material.ior } else { material.ior };
+        let fresnel = schlick(-ray.dir().dot(&hit_info.normal), eta);
+        reflect_weight += material.transparency * fresnel;
+        if let Some(dir) = refract(ray.dir(), &hit_info.normal, eta) {
+            refracted = Some((dir, material.transparency * (1.0 - fresnel)));
+        }
+    }
+
+    let mut colour = local_colour * local_weight;
+    if reflect_weight > 0.0 {
+        let reflect_ray = Ray::new(hit_info.point, reflect(ray.dir(), &hit_info.normal));
+        colour += cast_ray(scene, &reflect_ray, depth + 1, None, rng) * reflect_weight;
+    }
+    if let Some((dir, weight)) = refracted {
+        let refract_ray = Ray::new(hit_info.point, dir);
+        colour += cast_ray(scene, &refract_ray, depth + 1, None, rng) * weight;
+    }
+    colour
+}
+
+/// The ambient term, darkened by ambient occlusion if the scene has it, and Phong diffuse and
+/// specular in each light's colour, averaged over the lights that can see the hit point. Lights
+/// with area send several shadow rays, and count for however much of them gets through.
+fn colour_for_hit(scene: &SceneState, hit_info: &HitInformation, ray: &Ray, rng: &mut dyn RngCore) -> (Colour, Colour) {
+    let material = hit_info.material;
+    let mut direct = Colour::black();
+    let mut lit_by = 0;
+
+    for light in &scene.lights {
+        let samples = light.samples();
+        let mut from_light = Colour::black();
+        let mut visible = false;
+        for _ in 0..samples {
+            let light = match light.sample(&hit_info.point, rng) {
+                Some(light) => light,
+                None => continue
+            };
+            let new_ray: Ray = Ray::new(hit_info.point, light.to_light);
+            if scene.occluded(&new_ray, light.dist) {
+                continue;
+            }
+            visible = true;
+            let dot_n = new_ray.dir().dot(&hit_info.normal);
+            let diff_frac = dot_n.max(0.0) * material.diffuse;
+            let light_reflect = new_ray.dir() - 2.0 * dot_n * hit_info.normal;
+            let spec_frac = light_reflect.dot(ray.dir()).max(0.0).powf(material.specular_exp) * material.specular;
+            from_light += material.colour * light.colour * ((diff_frac + spec_frac) * (light.intensity / samples as f64));
+        }
+        if visible {
+            direct += from_light;
+            lit_by += 1;
+        }
+    }
+    if lit_by > 0 {
+        direct = direct / lit_by as f64;
+    }
+    let ambient = match &scene.ambient_occlusion {
+        Some(occlusion) => scene.ambient * occlusion.visibility(scene, &hit_info.point, &hit_info.normal, rng),
+        None => scene.ambient
+    };
+    (material.colour * ambient, direct)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::tracer::{Camera, Ray, SceneState};
+    use crate::tracer::colour::Colour;
+    use crate::tracer::geom::Drawable;
+    use crate::tracer::geom::plane::Plane;
+    use crate::tracer::geom::sphere::Sphere;
+    use crate::tracer::light::{Light, PointLight};
+
+    //Looking down at a floor, with a sphere off to the side that can shadow it from a light at -x
+    fn shade(lights: Vec<Box<dyn Light>>) -> Colour {
+        let geom: Vec<Box<dyn Drawable>> = vec![
+            Box::new(Plane::new(Vector3::zeros(), Vector3::new(0.0, 1.0, 0.0), Colour::new(1.0, 0.5, 0.25), 0.8, 0.0, 2.0)),
+            Box::new(Sphere::new(Vector3::new(-2.0, 2.0, 0.0), 0.5, Colour::white(), 0.8, 0.0, 2.0)),
+        ];
+        let camera = Camera::new(Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 90.0);
+        let scene = SceneState::new(geom, lights, camera, 0.1, Colour::black());
+        let down = Ray::new(Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        scene.trace(&down, None, &mut StdRng::seed_from_u64(0))
+    }
+
+    fn light(pos: Vector3<f64>, colour: Colour, intensity: f64) -> Box<dyn Light> {
+        Box::new(PointLight { pos, colour, intensity })
+    }
+
+    #[test]
+    fn test_lights_are_averaged() {
+        let overhead = Vector3::new(0.0, 4.0, 0.0);
+        let one = shade(vec![light(overhead, Colour::white(), 0.6)]);
+        assert!(one.r > 0.1);
+        //two lights average rather than add up, each lighting in its own colour, so a blue light
+        //beside a white one halves the red and green but leaves the blue
+        let two = shade(vec![light(overhead, Colour::white(), 0.6), light(overhead, Colour::new(0.0, 0.0, 1.0), 0.6)]);
+        approx::assert_ulps_eq!(two.r, 0.1 + (one.r - 0.1) / 2.0);
+        approx::assert_ulps_eq!(two.g, 0.05 + (one.g - 0.05) / 2.0);
+        approx::assert_ulps_eq!(two.b, one.b);
+        let dimmer = shade(vec![light(overhead, Colour::white(), 0.6), light(overhead, Colour::white(), 0.2)]);
+        approx::assert_ulps_eq!(dimmer.r, 0.1 + (one.r - 0.1) * (0.4 / 0.6));
+
+        //a light the sphere blocks is left out of the average altogether
+        let shadowed = shade(vec![light(overhead, Colour::white(), 0.6), light(Vector3::new(-4.0, 4.0, 0.0), Colour::white(), 0.6)]);
+        assert_eq!(shadowed, one);
+    }
+}
@@ -0,0 +1,231 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+
+use crate::tracer::{Camera, camera_basis, screen_to_coord_stride};
+
+/// The camera's frame worked out once for an image size, which every projection builds its
+/// rays from.
+#[derive(Debug, Copy, Clone)]
+pub struct View {
+    pub pos: Vector3<f64>,
+    pub forward: Vector3<f64>,
+    pub right: Vector3<f64>,
+    pub up: Vector3<f64>,
+    /// Perspective image plane, the corner of the top left pixel and the steps between pixels.
+    pub top_left: Vector3<f64>,
+    pub x_stride: Vector3<f64>,
+    pub y_stride: Vector3<f64>,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl View {
+    pub fn new(camera: &Camera, width: f64, height: f64) -> View {
+        let (right, up) = camera_basis(camera);
+        let (top_left, x_stride, y_stride) = screen_to_coord_stride(width, height, camera);
+        View {
+            pos: camera.pos,
+            forward: camera.dir.normalize(),
+            right,
+            up,
+            top_left,
+            x_stride,
+            y_stride,
+            width,
+            height,
+        }
+    }
+
+    /// Pixel coordinates as offsets from the centre of the image, with y up.
+    fn centred(&self, x: f64, y: f64) -> (f64, f64) {
+        (x - self.width / 2.0, self.height / 2.0 - y)
+    }
+}
+
+/// How positions on the image map to rays leaving the camera.
+pub trait Projection: fmt::Debug + Send + Sync {
+    /// Origin and direction of the ray through pixel `(x, y)`, `jitter` (each 0-1) moves it
+    /// across the pixel from its top left corner. `None` where the image falls outside what the
+    /// projection covers, which renders black.
+    fn ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64)) -> Option<(Vector3<f64>, Vector3<f64>)>;
+
+    /// Whether the rays share a focal plane a thin lens can focus on.
+    fn supports_lens(&self) -> bool {
+        false
+    }
+}
+
+/// A pinhole frustum with the camera's `fov` across the width of the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Perspective;
+
+impl Projection for Perspective {
+    fn ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64)) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let y_pixel_pos = view.top_left + (view.y_stride * y);
+        let pixel_pos = y_pixel_pos + (view.x_stride * x) + (view.x_stride * jitter.0) + (view.y_stride * jitter.1);
+        Some((view.pos, pixel_pos - view.pos))
+    }
+
+    fn supports_lens(&self) -> bool {
+        true
+    }
+}
+
+/// Parallel rays from a rectangle `width` world units across, centred on the camera.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Orthographic {
+    pub width: f64,
+}
+
+impl Projection for Orthographic {
+    fn ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64)) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let (across, up) = view.centred(x + jitter.0, y + jitter.1);
+        let scale = self.width / view.width;
+        Some((view.pos + view.right * (across * scale) + view.up * (up * scale), view.forward))
+    }
+
+    fn supports_lens(&self) -> bool {
+        true
+    }
+}
+
+/// Equidistant circular fisheye, `fov` degrees across a circle filling the image's shorter side.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fisheye {
+    pub fov: f64,
+}
+
+impl Projection for Fisheye {
+    fn ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64)) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let (across, up) = view.centred(x + jitter.0, y + jitter.1);
+        let radius = (across * across + up * up).sqrt() / (view.width.min(view.height) / 2.0);
+        if radius > 1.0 {
+            return None;
+        }
+        let (sin_theta, cos_theta) = (radius * self.fov.to_radians() / 2.0).sin_cos();
+        let (sin_phi, cos_phi) = up.atan2(across).sin_cos();
+        let dir = view.forward * cos_theta + (view.right * cos_phi + view.up * sin_phi) * sin_theta;
+        Some((view.pos, dir))
+    }
+}
+
+/// Latitude-longitude panorama, all the way round across the width and pole to pole down the
+/// height, with the view direction in the middle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn ray(&self, view: &View, x: f64, y: f64, jitter: (f64, f64)) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let longitude = ((x + jitter.0) / view.width - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - (y + jitter.1) / view.height) * PI;
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let (sin_long, cos_long) = longitude.sin_cos();
+        let dir = view.forward * (cos_lat * cos_long) + view.right * (cos_lat * sin_long) + view.up * sin_lat;
+        Some((view.pos, dir))
+    }
+}
+
+/// The projections a scene can pick with its camera's `type`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProjectionType {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+const PROJECTION_NAMES: [(&str, ProjectionType); 4] = [
+    ("perspective", ProjectionType::Perspective),
+    ("orthographic", ProjectionType::Orthographic),
+    ("fisheye", ProjectionType::Fisheye),
+    ("equirectangular", ProjectionType::Equirectangular),
+];
+
+impl FromStr for ProjectionType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ProjectionType, String> {
+        PROJECTION_NAMES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, projection)| *projection)
+            .ok_or_else(|| format!("unknown camera type '{}', expected one of {}", name,
+                                   PROJECTION_NAMES.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for ProjectionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = PROJECTION_NAMES.iter().find(|(_, projection)| projection == self).map(|(name, _)| *name).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use approx;
+    use nalgebra::Vector3;
+
+    use crate::tracer::Camera;
+    use crate::tracer::projection::{Equirectangular, Fisheye, Orthographic, Perspective, Projection,
+                                    ProjectionType, View, PROJECTION_NAMES};
+
+    fn view() -> View {
+        View::new(&Camera::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 2.0), 90.0), 100.0, 50.0)
+    }
+
+    #[test]
+    fn test_names() {
+        for (name, projection) in PROJECTION_NAMES.iter() {
+            assert_eq!(name.parse::<ProjectionType>().unwrap(), *projection);
+            assert_eq!(projection.to_string(), *name);
+        }
+        assert!("pinhole".parse::<ProjectionType>().unwrap_err().contains("fisheye"));
+    }
+
+    #[test]
+    fn test_perspective_and_orthographic() {
+        let view = view();
+        let (orig, dir) = Perspective.ray(&view, 50.0, 25.0, (0.0, 0.0)).unwrap();
+        assert_eq!(orig, Vector3::zeros());
+        approx::assert_ulps_eq!(dir, Vector3::new(0.0, 0.0, 2.0));
+
+        let ortho = Orthographic { width: 4.0 };
+        let (orig, dir) = ortho.ray(&view, 0.0, 0.0, (0.0, 0.0)).unwrap();
+        approx::assert_ulps_eq!(orig, Vector3::new(-2.0, 1.0, 0.0));
+        approx::assert_ulps_eq!(dir, Vector3::new(0.0, 0.0, 1.0));
+        let (orig, _) = ortho.ray(&view, 99.0, 49.0, (1.0, 1.0)).unwrap();
+        approx::assert_ulps_eq!(orig, Vector3::new(2.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_fisheye() {
+        let view = view();
+        let fisheye = Fisheye { fov: 180.0 };
+        let (_, centre) = fisheye.ray(&view, 50.0, 25.0, (0.0, 0.0)).unwrap();
+        approx::assert_ulps_eq!(centre, Vector3::new(0.0, 0.0, 1.0));
+        //the edge of the circle looks out sideways, half way to it is 45 degrees
+        let (_, edge) = fisheye.ray(&view, 75.0, 25.0, (0.0, 0.0)).unwrap();
+        approx::assert_abs_diff_eq!(edge, Vector3::new(1.0, 0.0, 0.0), epsilon = 1e-12);
+        let (_, half) = fisheye.ray(&view, 50.0, 12.5, (0.0, 0.0)).unwrap();
+        approx::assert_abs_diff_eq!(half, Vector3::new(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2), epsilon = 1e-12);
+        assert!(fisheye.ray(&view, 10.0, 25.0, (0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let view = view();
+        let (_, centre) = Equirectangular.ray(&view, 50.0, 25.0, (0.0, 0.0)).unwrap();
+        approx::assert_ulps_eq!(centre, Vector3::new(0.0, 0.0, 1.0));
+        let (_, behind) = Equirectangular.ray(&view, 0.0, 25.0, (0.0, 0.0)).unwrap();
+        approx::assert_abs_diff_eq!(behind, Vector3::new(0.0, 0.0, -1.0), epsilon = 1e-12);
+        let (_, right) = Equirectangular.ray(&view, 75.0, 25.0, (0.0, 0.0)).unwrap();
+        approx::assert_abs_diff_eq!(right, Vector3::new(1.0, 0.0, 0.0), epsilon = 1e-12);
+        let (_, top) = Equirectangular.ray(&view, 50.0, 0.0, (0.0, 0.0)).unwrap();
+        approx::assert_abs_diff_eq!(top, Vector3::new(0.0, 1.0, 0.0), epsilon = 1e-12);
+    }
+}
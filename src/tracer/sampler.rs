@@ -0,0 +1,263 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::prelude::*;
+
+/// Where within a pixel its samples go.
+pub trait Sampler: fmt::Debug + Send + Sync {
+    /// `count` offsets across the pixel from its top left corner, each coordinate 0-1. Any
+    /// randomness comes from `rng`, so the same rng state always gives the same samples.
+    fn pixel_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)>;
+
+    /// A second 2D dimension for the same `count` samples, spread over the unit square as evenly
+    /// as the pixel offsets are but paired with them in an unrelated order, for where on the lens
+    /// each sample's ray starts.
+    fn lens_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)>;
+}
+
+/// Every sample anywhere in the pixel, independently of the others.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn pixel_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        (0..count).map(|_| (rng.gen(), rng.gen())).collect()
+    }
+
+    fn lens_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        self.pixel_samples(count, rng)
+    }
+}
+
+/// The pixel split into a grid of cells, as square as the sample count allows, with a sample
+/// jittered within each. When the count doesn't fill the grid the empty cells are chosen at
+/// random, so no part of the pixel is always left out.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stratified;
+
+impl Sampler for Stratified {
+    fn pixel_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        let columns = (count as f64).sqrt().ceil().max(1.0) as u32;
+        let rows = count.div_ceil(columns);
+        let mut cells: Vec<u32> = (0..columns * rows).collect();
+        //a partial shuffle picks which cells get used
+        for i in 0..count as usize {
+            let pick = rng.gen_range(i, cells.len());
+            cells.swap(i, pick);
+        }
+        cells[..count as usize].iter()
+            .map(|cell| {
+                let (column, row) = (cell % columns, cell / columns);
+                ((column as f64 + rng.gen::<f64>()) / columns as f64, (row as f64 + rng.gen::<f64>()) / rows as f64)
+            })
+            .collect()
+    }
+
+    //the cells already come out in a random order
+    fn lens_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        self.pixel_samples(count, rng)
+    }
+}
+
+/// The Halton sequence in bases 2 and 3, shifted by a random amount per pixel and wrapped round
+/// so neighbouring pixels don't share the same pattern. The lens uses bases 5 and 7.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Halton;
+
+impl Sampler for Halton {
+    fn pixel_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        let (shift_x, shift_y): (f64, f64) = (rng.gen(), rng.gen());
+        (0..count)
+            .map(|i| ((radical_inverse(i, 2) + shift_x).fract(), (radical_inverse(i, 3) + shift_y).fract()))
+            .collect()
+    }
+
+    fn lens_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        let (shift_x, shift_y): (f64, f64) = (rng.gen(), rng.gen());
+        (0..count)
+            .map(|i| ((radical_inverse(i, 5) + shift_x).fract(), (radical_inverse(i, 7) + shift_y).fract()))
+            .collect()
+    }
+}
+
+/// The first two dimensions of the Sobol sequence, with a random binary scramble per pixel that
+/// keeps every power of two count of samples evenly spread. The lens gets the same two dimensions
+/// scrambled again and shuffled, so they don't line up with the pixel offsets.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sobol;
+
+impl Sampler for Sobol {
+    fn pixel_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        let (scramble_x, scramble_y) = (rng.next_u32(), rng.next_u32());
+        (0..count)
+            .map(|i| (to_unit(i.reverse_bits() ^ scramble_x), to_unit(sobol_second(i) ^ scramble_y)))
+            .collect()
+    }
+
+    fn lens_samples(&self, count: u32, rng: &mut dyn RngCore) -> Vec<(f64, f64)> {
+        let mut samples = self.pixel_samples(count, rng);
+        samples.shuffle(rng);
+        samples
+    }
+}
+
+/// Reflects the digits of `index` in `base` about the decimal point, 0.d1d2d3... for index
+/// ...d3d2d1.
+fn radical_inverse(mut index: u32, base: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut scale = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+    }
+    result
+}
+
+/// Second Sobol dimension as 32 bits of binary fraction, from the direction numbers of the
+/// polynomial x + 1.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut result = 0;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// A 32 bit binary fraction as a float in [0, 1).
+fn to_unit(bits: u32) -> f64 {
+    bits as f64 / (1u64 << 32) as f64
+}
+
+/// The samplers a scene or the command line can pick by name.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum SamplerType {
+    Independent,
+    #[default]
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerType {
+    pub fn sampler(&self) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(Independent),
+            SamplerType::Stratified => Box::new(Stratified),
+            SamplerType::Halton => Box::new(Halton),
+            SamplerType::Sobol => Box::new(Sobol),
+        }
+    }
+}
+
+const SAMPLER_NAMES: [(&str, SamplerType); 4] = [
+    ("independent", SamplerType::Independent),
+    ("stratified", SamplerType::Stratified),
+    ("halton", SamplerType::Halton),
+    ("sobol", SamplerType::Sobol),
+];
+
+impl FromStr for SamplerType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<SamplerType, String> {
+        SAMPLER_NAMES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, sampler)| *sampler)
+            .ok_or_else(|| format!("unknown sampler '{}', expected one of {}", name,
+                                   SAMPLER_NAMES.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for SamplerType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = SAMPLER_NAMES.iter().find(|(_, sampler)| sampler == self).map(|(name, _)| *name).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::tracer::sampler::{radical_inverse, sobol_second, to_unit, Halton, Sampler, SamplerType, Sobol,
+                                 Stratified, SAMPLER_NAMES};
+
+    #[test]
+    fn test_names() {
+        for (name, sampler) in SAMPLER_NAMES.iter() {
+            assert_eq!(name.parse::<SamplerType>().unwrap(), *sampler);
+            assert_eq!(sampler.to_string(), *name);
+        }
+        assert!("random".parse::<SamplerType>().unwrap_err().contains("sobol"));
+    }
+
+    #[test]
+    fn test_samples_stay_in_the_pixel() {
+        for (_, sampler) in SAMPLER_NAMES.iter() {
+            let sampler = sampler.sampler();
+            let mut rng = StdRng::seed_from_u64(1);
+            for count in [1, 2, 7, 16, 33].iter() {
+                for samples in [sampler.pixel_samples(*count, &mut rng), sampler.lens_samples(*count, &mut rng)].iter() {
+                    assert_eq!(samples.len(), *count as usize);
+                    assert!(samples.iter().all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)));
+                }
+            }
+            //the same rng state gives the same samples
+            let (mut first, mut second) = (StdRng::seed_from_u64(9), StdRng::seed_from_u64(9));
+            assert_eq!(sampler.pixel_samples(8, &mut first), sampler.pixel_samples(8, &mut second));
+        }
+    }
+
+    //How many of the samples land in each of the n by n cells of the pixel
+    fn cell_counts(samples: &[(f64, f64)], n: usize) -> Vec<usize> {
+        let mut counts = vec![0; n * n];
+        for (x, y) in samples {
+            counts[(y * n as f64) as usize * n + (x * n as f64) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_evenly_spread() {
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(cell_counts(&Stratified.pixel_samples(16, &mut rng), 4), vec![1; 16]);
+        //five of a three by two grid, no cell twice
+        assert!(cell_counts(&Stratified.pixel_samples(5, &mut rng), 2).iter().all(|&count| count <= 2));
+        //scrambled Sobol points are a (0, 2) net, one point in every elementary interval
+        assert_eq!(cell_counts(&Sobol.pixel_samples(16, &mut rng), 4), vec![1; 16]);
+        let halton = Halton.pixel_samples(64, &mut rng);
+        assert!(cell_counts(&halton, 4).iter().all(|&count| (2..=6).contains(&count)));
+
+        //the lens dimension is just as even
+        for sampler in [&Stratified as &dyn Sampler, &Sobol].iter() {
+            assert_eq!(cell_counts(&sampler.lens_samples(16, &mut rng), 4), vec![1; 16]);
+        }
+        //Sobol's lens samples are its pixel samples in another order, rather than paired with
+        //their own pixel offset
+        let pixel = Sobol.pixel_samples(16, &mut StdRng::seed_from_u64(4));
+        let mut lens = Sobol.lens_samples(16, &mut StdRng::seed_from_u64(4));
+        assert_ne!(lens, pixel);
+        lens.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut sorted = pixel;
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(lens, sorted);
+        let halton = Halton.lens_samples(64, &mut rng);
+        assert!(cell_counts(&halton, 4).iter().all(|&count| (2..=6).contains(&count)));
+    }
+
+    #[test]
+    fn test_sequences() {
+        let halton: Vec<f64> = (0..4).map(|i| radical_inverse(i, 3)).collect();
+        assert_eq!(halton, vec![0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0]);
+        let sobol: Vec<f64> = (0..4).map(|i| to_unit(sobol_second(i))).collect();
+        assert_eq!(sobol, vec![0.0, 0.5, 0.75, 0.25]);
+    }
+}
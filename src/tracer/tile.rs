@@ -0,0 +1,64 @@
+use crate::Resolution;
+
+pub const TILE_SIZE: u16 = 32;
+
+/// A rectangular region of the image, in pixels, rendered as one unit of work.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tile {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Tile {
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+/// Splits the image into tiles in scanline order, the tiles on the right and bottom edges are
+/// clipped to the image.
+pub fn tiles_for(res: &Resolution) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    let mut y = 0;
+    while y < res.height {
+        let height = TILE_SIZE.min(res.height - y);
+        let mut x = 0;
+        while x < res.width {
+            let width = TILE_SIZE.min(res.width - x);
+            tiles.push(Tile { x, y, width, height });
+            x += width;
+        }
+        y += height;
+    }
+    tiles
+}
+
+/// Copies a tile's pixels into its region of a full frame, each pixel being `stride` elements
+/// long, 4 for RGBA bytes or 1 for a frame of colours.
+pub fn copy_to_frame<T: Copy>(tile: &Tile, pixels: &[T], frame: &mut [T], res: &Resolution, stride: usize) {
+    let row_len = tile.width as usize * stride;
+    for row in 0..tile.height as usize {
+        let frame_loc = ((tile.y as usize + row) * res.width as usize + tile.x as usize) * stride;
+        let tile_loc = row * row_len;
+        frame[frame_loc..frame_loc + row_len].copy_from_slice(&pixels[tile_loc..tile_loc + row_len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Resolution;
+    use crate::tracer::tile::{tiles_for, TILE_SIZE};
+
+    #[test]
+    fn test_tiles_cover_image() {
+        let res = Resolution { width: 70, height: 33 };
+        let tiles = tiles_for(&res);
+        assert_eq!(tiles.len(), 6);
+        let covered: usize = tiles.iter().map(|tile| tile.pixel_count()).sum();
+        assert_eq!(covered, 70 * 33);
+        let last = tiles.last().unwrap();
+        assert_eq!((last.x, last.y, last.width, last.height), (2 * TILE_SIZE, TILE_SIZE, 6, 1));
+    }
+}
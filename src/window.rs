@@ -31,7 +31,7 @@ pub fn run(res:Resolution,frame:&FrameBuffer) -> GameResult {
 }
 
 impl WindowState<'_> {
-    fn new(res: Resolution, frame: &FrameBuffer) -> WindowState {
+    fn new(res: Resolution, frame: &FrameBuffer) -> WindowState<'_> {
         WindowState {
             res,
             frame